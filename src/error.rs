@@ -15,3 +15,25 @@ pub enum FsError {
     #[error(transparent)]
     IoError(#[from] std::io::Error),
 }
+
+#[derive(Error, Debug)]
+pub enum FrontmatterError {
+    #[error("Frontmatter is missing closing triple dash")]
+    MissingYamlEnd,
+    #[error("Frontmatter is missing closing triple plus")]
+    MissingTomlEnd,
+    #[error("JSON frontmatter is missing closing brace or triple dash")]
+    MissingJsonEnd,
+    #[error("Failed to parse YAML frontmatter: {0}")]
+    Yaml(String),
+    #[error("Failed to parse TOML frontmatter: {0}")]
+    Toml(String),
+    #[error("Failed to parse JSON frontmatter: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Invalid frontmatter in {file}: `{field}` {reason}")]
+    Invalid {
+        file: String,
+        field: String,
+        reason: String,
+    },
+}
@@ -188,14 +188,13 @@ mod tests {
     #[actix_web::test]
     async fn relativize() {
         let p = HtmlParser::default().relativize_urls();
-        let res = p.process(&mut State::new("dist").unwrap(), &Item {
+        let res = p.process(&mut State::new("dist", "test/templates").unwrap(), &Item {
             path: PathBuf::from("/posts/thing1.html"),
             bytes: b"<html lang=\"en\"><head><link rel=\"stylesheet\" href=\"/css/default.css\"></head><body><a href=\"/another/file.html\">Some link</a><img src=\"/images/profile.png\"></body></html>".to_vec(),
             properties: HashMap::new(),
-            cache: HashMap::new(),
         }).await.unwrap().bytes;
         let res = String::from_utf8(res).unwrap();
-        let expected = format!("<html lang=\"en\"><head><link rel=\"stylesheet\" href=\"./../css/default.css\"></head><body><a href=\"./../another/file.html\">Some link</a><img src=\"./../images/profile.png\"></body></html>");
+        let expected = format!("<html lang=en><link href=./../css/default.css rel=stylesheet><body><a href=./../another/file.html>Some link</a><img src=./../images/profile.png>");
 
         assert_eq!(expected, res);
     }
@@ -203,14 +202,13 @@ mod tests {
     #[actix_web::test]
     async fn relativize_with_unapplied_caching() {
         let p = HtmlParser::default().relativize_urls().cache_linked_resources();
-        let res = p.process(&mut State::new("dist").unwrap(), &Item {
+        let res = p.process(&mut State::new("dist", "test/templates").unwrap(), &Item {
             path: PathBuf::from("/posts/thing1.html"),
             bytes: b"<html lang=\"en\"><head><link rel=\"stylesheet\" href=\"/css/default.css\"></head><body><a href=\"/another/file.html\">Some link</a><img src=\"/images/profile.png\"></body></html>".to_vec(),
             properties: HashMap::new(),
-            cache: HashMap::new(),
         }).await.unwrap().bytes;
         let res = String::from_utf8(res).unwrap();
-        let expected = format!("<html lang=\"en\"><head><link rel=\"stylesheet\" href=\"./../css/default.css\"></head><body><a href=\"./../another/file.html\">Some link</a><img src=\"./../images/profile.png\"></body></html>");
+        let expected = format!("<html lang=en><link href=./../css/default.css rel=stylesheet><body><a href=./../another/file.html>Some link</a><img src=./../images/profile.png>");
 
         assert_eq!(expected, res);
     }
@@ -0,0 +1,380 @@
+use super::{extension::MarkdownExtension, footnote, html, math};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Block {
+    Paragraph(Vec<Inline>),
+    // level, id once the document's headings have been given theirs, and text
+    Heading(usize, Option<String>, Vec<Inline>),
+    Rule,
+    Code(Option<String>, String),
+    Math(String),
+    List(List),
+    Table(Table),
+    Callout(Callout),
+    Definitions(Vec<Definition>),
+    // lines, each with the marker it started with
+    Extension(MarkdownExtension, Vec<(String, Vec<Inline>)>),
+    Html(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Inline {
+    // escaped when rendered, raw html written in the source becomes `Html`
+    Text(String),
+    Code(String),
+    // tex source, and whether it's display math
+    Math(bool, String),
+    Bold(Vec<Inline>),
+    Italic(Vec<Inline>),
+    Strikethrough(Vec<Inline>),
+    Underline(Vec<Inline>),
+    // destination, title and text
+    Link(String, Option<String>, Vec<Inline>),
+    // source, title and alt text
+    Image(String, Option<String>, Vec<Inline>),
+    // name, and once the document's references are numbered, its number and which reference to that footnote it is
+    FootnoteReference(String, Option<(usize, usize)>),
+    SoftBreak,
+    LineBreak,
+    Extension(MarkdownExtension, Vec<Inline>),
+    Html(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct List {
+    // only ordered lists have a start number
+    pub start: Option<u64>,
+    pub items: Vec<ListItem>,
+    pub loose: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ListItem {
+    // whether a `[ ]` or `[x]` task item is checked, none for other items
+    pub task: Option<bool>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Table {
+    pub alignments: Vec<Alignment>,
+    pub header: Vec<Vec<Inline>>,
+    pub rows: Vec<Vec<Vec<Inline>>>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Callout {
+    // lowercased, as used in its class
+    pub kind: String,
+    pub title: Vec<Inline>,
+    pub blocks: Vec<Block>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Definition {
+    pub term: Vec<Inline>,
+    pub definitions: Vec<Vec<Inline>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Alignment {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+// calls `f` on every block, including the ones nested in list items and callouts
+pub fn visit_blocks<F: FnMut(&mut Block)>(blocks: &mut [Block], f: &mut F) {
+    for block in blocks {
+        f(block);
+
+        match block {
+            Block::List(list) => {
+                for item in &mut list.items {
+                    visit_blocks(&mut item.blocks, f);
+                }
+            },
+            Block::Callout(callout) => visit_blocks(&mut callout.blocks, f),
+            _ => {},
+        }
+    }
+}
+
+// calls `f` on the inlines directly inside every block, such as a paragraph's or each table cell's
+pub fn visit_block_inlines<F: FnMut(&mut Vec<Inline>)>(blocks: &mut [Block], f: &mut F) {
+    visit_blocks(blocks, &mut |block| match block {
+        Block::Paragraph(inlines) | Block::Heading(_, _, inlines) => f(inlines),
+        Block::Callout(callout) => f(&mut callout.title),
+        Block::Table(table) => table.header
+            .iter_mut()
+            .chain(table.rows.iter_mut().flatten())
+            .for_each(&mut *f),
+        Block::Definitions(definitions) => definitions
+            .iter_mut()
+            .for_each(|definition| {
+                f(&mut definition.term);
+                definition.definitions.iter_mut().for_each(&mut *f);
+            }),
+        Block::Extension(_, lines) => lines
+            .iter_mut()
+            .for_each(|(_, line)| f(line)),
+        _ => {},
+    });
+}
+
+// calls `f` on every inline, parents before their children
+pub fn visit_inlines<F: FnMut(&mut Inline)>(blocks: &mut [Block], f: &mut F) {
+    visit_block_inlines(blocks, &mut |inlines| visit(inlines, f));
+}
+
+fn visit<F: FnMut(&mut Inline)>(inlines: &mut [Inline], f: &mut F) {
+    for inline in inlines {
+        f(inline);
+
+        match inline {
+            Inline::Bold(inner)
+            | Inline::Italic(inner)
+            | Inline::Strikethrough(inner)
+            | Inline::Underline(inner)
+            | Inline::Link(_, _, inner)
+            | Inline::Image(_, _, inner)
+            | Inline::Extension(_, inner) => visit(inner, f),
+            _ => {},
+        }
+    }
+}
+
+pub fn html(blocks: &[Block]) -> String {
+    render(blocks, false)
+}
+
+// tight content (list items, inline fragments) leaves its paragraphs unwrapped
+pub(super) fn render(blocks: &[Block], tight: bool) -> String {
+    blocks
+        .iter()
+        .map(|block| match block {
+            Block::Paragraph(inner) if tight => inline_html(inner),
+            Block::Paragraph(inner) => format!("<p>{}</p>", inline_html(inner)),
+            Block::Heading(level, Some(id), inner) => format!(
+                "<h{} id=\"{}\">{}</h{}>",
+                level,
+                html_escape::encode_double_quoted_attribute(id),
+                inline_html(inner),
+                level,
+            ),
+            Block::Heading(level, None, inner) => format!("<h{}>{}</h{}>", level, inline_html(inner), level),
+            Block::Rule => "<hr/>".to_owned(),
+            Block::Code(info, code) => {
+                let code = html_escape::encode_safe(code);
+
+                match info {
+                    Some(info) => match info.rsplit_once('.') {
+                        Some((_, language)) => format!("<pre><small>{}</small><code class=\"language-{}\">{}</code></pre>", html_escape::encode_safe(info), html_escape::encode_double_quoted_attribute(language), code),
+                        None => format!("<pre><code class=\"language-{}\">{}</code></pre>", html_escape::encode_double_quoted_attribute(info), code),
+                    },
+                    None => format!("<pre><code>{}</code></pre>", code),
+                }
+            },
+            Block::Math(tex) => math::render(tex, true),
+            Block::List(list) => {
+                let items = list.items
+                    .iter()
+                    .map(|item| {
+                        let html = render(&item.blocks, !list.loose);
+                        let checkbox = match item.task {
+                            Some(true) => "<input type=\"checkbox\" disabled checked/> ",
+                            Some(false) => "<input type=\"checkbox\" disabled/> ",
+                            None => return format!("<li>{}</li>", html),
+                        };
+
+                        // inside the first paragraph of a loose item, so it sits on the same line
+                        match html.strip_prefix("<p>") {
+                            Some(html) => format!("<li class=\"task\"><p>{}{}</li>", checkbox, html),
+                            None => format!("<li class=\"task\">{}{}</li>", checkbox, html),
+                        }
+                    })
+                    .collect::<Vec<String>>()
+                    .concat();
+
+                match list.start {
+                    None => format!("<ul>{}</ul>", items),
+                    Some(1) => format!("<ol>{}</ol>", items),
+                    Some(start) => format!("<ol start=\"{}\">{}</ol>", start, items),
+                }
+            },
+            Block::Table(table) => {
+                let mut html = format!("<table><thead>{}</thead>", row("th", &table.header, &table.alignments));
+
+                if !table.rows.is_empty() {
+                    html.push_str("<tbody>");
+
+                    for r in &table.rows {
+                        html.push_str(&row("td", r, &table.alignments));
+                    }
+
+                    html.push_str("</tbody>");
+                }
+
+                html.push_str("</table>");
+
+                html
+            },
+            Block::Callout(callout) => format!(
+                "<aside class=\"callout callout-{}\"><p class=\"callout-title\">{}</p>{}</aside>",
+                html_escape::encode_double_quoted_attribute(&callout.kind),
+                inline_html(&callout.title),
+                render(&callout.blocks, false),
+            ),
+            Block::Definitions(definitions) => format!("<dl>{}</dl>", definitions
+                .iter()
+                .map(|definition| format!("<dt>{}</dt>{}", inline_html(&definition.term), definition.definitions
+                    .iter()
+                    .map(|inner| format!("<dd>{}</dd>", inline_html(inner)))
+                    .collect::<Vec<String>>()
+                    .concat()))
+                .collect::<Vec<String>>()
+                .concat()),
+            Block::Extension(extension, lines) => match extension {
+                MarkdownExtension::Block(_, line_wrapper, block_wrapper) => block_wrapper(lines
+                    .iter()
+                    .map(|(_, line)| line_wrapper(inline_html(line)))
+                    .collect()),
+                MarkdownExtension::Inline(..) => lines
+                    .iter()
+                    .map(|(_, line)| inline_html(line))
+                    .collect::<Vec<String>>()
+                    .concat(),
+            },
+            Block::Html(html) => html.clone(),
+        })
+        .collect::<Vec<String>>()
+        .concat()
+}
+
+fn row(tag: &str, cells: &[Vec<Inline>], alignments: &[Alignment]) -> String {
+    let cells = cells
+        .iter()
+        .zip(alignments)
+        .map(|(cell, alignment)| {
+            let style = match alignment {
+                Alignment::None => "",
+                Alignment::Left => " style=\"text-align: left\"",
+                Alignment::Center => " style=\"text-align: center\"",
+                Alignment::Right => " style=\"text-align: right\"",
+            };
+
+            format!("<{}{}>{}</{}>", tag, style, inline_html(cell), tag)
+        })
+        .collect::<Vec<String>>()
+        .concat();
+
+    format!("<tr>{}</tr>", cells)
+}
+
+fn inline_html(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            Inline::Text(text) => html::escape(text),
+            Inline::Code(code) => format!("<code>{}</code>", html_escape::encode_safe(code)),
+            Inline::Math(display, tex) => math::render(tex, *display),
+            Inline::Bold(inner) => format!("<b>{}</b>", inline_html(inner)),
+            Inline::Italic(inner) => format!("<i>{}</i>", inline_html(inner)),
+            Inline::Strikethrough(inner) => format!("<s>{}</s>", inline_html(inner)),
+            Inline::Underline(inner) => format!("<u>{}</u>", inline_html(inner)),
+            Inline::Link(href, title, inner) => format!("<a href=\"{}\"{}>{}</a>", html_escape::encode_double_quoted_attribute(href), title_attribute(title), inline_html(inner)),
+            Inline::Image(src, title, alt) => format!("<img src=\"{}\" alt=\"{}\"{}/>", html_escape::encode_double_quoted_attribute(src), alt_attribute(alt), title_attribute(title)),
+            Inline::FootnoteReference(_, Some((number, count))) => format!(
+                "<sup class=\"footnote-ref\" id=\"{}\"><a href=\"#fn-{}\">{}</a></sup>",
+                footnote::ref_id(*number, *count),
+                number,
+                number,
+            ),
+            // outside of a rendered document, where there's nothing to link to
+            Inline::FootnoteReference(name, None) => format!("[^{}]", html_escape::encode_text(name)),
+            Inline::SoftBreak => "\n".to_owned(),
+            Inline::LineBreak => "<br/>".to_owned(),
+            Inline::Extension(extension, inner) => match extension {
+                MarkdownExtension::Inline(_, _, wrapper) => wrapper(inline_html(inner)),
+                MarkdownExtension::Block(..) => inline_html(inner),
+            },
+            Inline::Html(html) => html.clone(),
+        })
+        .collect::<Vec<String>>()
+        .concat()
+}
+
+// alt text can only be plain text, with any entities in it decoded so they aren't escaped twice
+fn alt_attribute(alt: &[Inline]) -> String {
+    html_escape::encode_double_quoted_attribute(&html_escape::decode_html_entities(&inline_text(alt))).into_owned()
+}
+
+fn title_attribute(title: &Option<String>) -> String {
+    match title {
+        Some(title) => format!(" title=\"{}\"", html_escape::encode_double_quoted_attribute(title)),
+        None => String::new(),
+    }
+}
+
+// drops markup, raw html and footnote references, keeping one blank line between blocks
+pub fn text(blocks: &[Block]) -> String {
+    blocks
+        .iter()
+        .map(|block| match block {
+            Block::Paragraph(inner) | Block::Heading(_, _, inner) => inline_text(inner),
+            Block::Code(_, code) | Block::Math(code) => code.trim_end().to_owned(),
+            Block::List(list) => list.items
+                .iter()
+                .map(|item| text(&item.blocks))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Block::Table(table) => std::iter::once(&table.header)
+                .chain(&table.rows)
+                .map(|r| r
+                    .iter()
+                    .map(|cell| inline_text(cell))
+                    .collect::<Vec<String>>()
+                    .join(" "))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Block::Callout(callout) => format!("{}\n\n{}", inline_text(&callout.title), text(&callout.blocks))
+                .trim_end()
+                .to_owned(),
+            Block::Definitions(definitions) => definitions
+                .iter()
+                .flat_map(|definition| [&definition.term].into_iter().chain(&definition.definitions))
+                .map(|inner| inline_text(inner))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Block::Extension(_, lines) => lines
+                .iter()
+                .map(|(_, line)| inline_text(line))
+                .collect::<Vec<String>>()
+                .join("\n"),
+            Block::Rule | Block::Html(_) => String::new(),
+        })
+        .filter(|s| !s.is_empty())
+        .collect::<Vec<String>>()
+        .join("\n\n")
+}
+
+pub(super) fn inline_text(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            Inline::Text(text) | Inline::Code(text) | Inline::Math(_, text) => text.clone(),
+            Inline::Bold(inner)
+            | Inline::Italic(inner)
+            | Inline::Strikethrough(inner)
+            | Inline::Underline(inner)
+            | Inline::Link(_, _, inner)
+            | Inline::Image(_, _, inner)
+            | Inline::Extension(_, inner) => inline_text(inner),
+            Inline::SoftBreak => " ".to_owned(),
+            Inline::LineBreak => "\n".to_owned(),
+            Inline::FootnoteReference(..) | Inline::Html(_) => String::new(),
+        })
+        .collect::<Vec<String>>()
+        .concat()
+}
@@ -0,0 +1,92 @@
+use chumsky::{prelude::*, text::newline};
+
+use crate::parser::Extra;
+
+use super::{ast::{Block, Callout, Inline}, diagnostic, document, inline, Syntax};
+
+// `> [!NOTE]` alerts and `:::tip` containers, both holding nested markdown
+pub(super) fn callout<'src>(syntax: Syntax) -> impl Parser<'src, &'src str, Block, Extra<'src>> + Clone {
+    let kind = any()
+        .filter(|c: &char| c.is_ascii_alphanumeric() || "-_".contains(*c))
+        .repeated()
+        .at_least(1)
+        .to_slice();
+    let line = any()
+        .and_is(newline().not())
+        .repeated()
+        .to_slice();
+    let closing = just(":::")
+        .then(one_of(" \t").repeated())
+        .then(newline().or(end()).rewind());
+
+    let alert = just('>')
+        .then(just(' ').or_not())
+        .ignore_then(kind.delimited_by(just("[!"), just(']')))
+        .then(line)
+        .then(newline()
+            .ignore_then(just('>')
+                .ignore_then(line))
+            .repeated()
+            .collect::<Vec<&str>>())
+        .map(|((kind, title), lines): ((&str, &str), Vec<&str>)| {
+            let lines = lines
+                .iter()
+                .map(|line| line.strip_prefix(' ').unwrap_or(line))
+                .collect::<Vec<&str>>();
+
+            (kind, title, lines)
+        });
+    // containers can hold other containers, so a closing `:::` only ends the innermost one
+    let container = recursive(|container| just(":::")
+        .ignore_then(kind)
+        .then(line)
+        .then(newline()
+            .ignore_then(choice((
+                container.ignored(),
+                line
+                    .and_is(closing.not())
+                    .ignored(),
+            )))
+            .repeated()
+            .to_slice())
+        .then_ignore(newline())
+        .then_ignore(closing))
+        .map(|((kind, title), body): ((&str, &str), &str)| (kind, title, vec![body.trim_start_matches('\n')]));
+
+    choice((
+        alert,
+        container,
+    ))
+        .validate(move |(kind, title, lines), e, emitter| {
+            let mut errors = Vec::new();
+            let callout = build(e.slice(), kind, title, &lines, &syntax, &mut errors);
+            diagnostic::emit(&errors, e.slice(), "callout", e.span(), emitter);
+
+            callout
+        })
+}
+
+// the title and lines are slices of `src`, errors in them are pushed to `errors` at their offsets in it
+fn build<'a>(src: &str, kind: &str, title: &str, lines: &[&str], syntax: &Syntax, errors: &mut Vec<Rich<'a, char>>) -> Block {
+    let kind = kind.to_lowercase();
+    let title = match title.trim() {
+        // the kind itself, capitalized
+        "" => vec![Inline::Text(kind[..1].to_uppercase() + &kind[1..])],
+        title => {
+            let start = title.as_ptr() as usize - src.as_ptr() as usize;
+            let (inlines, e) = inline(syntax.clone()).parse(title).into_output_errors();
+            errors.extend(e.iter().map(|e| diagnostic::relocate(e, title, "callout title", |offset| start + offset)));
+
+            inlines.unwrap_or_default()
+        },
+    };
+    let (body, map) = diagnostic::join(lines, src);
+    let (blocks, e) = document(syntax).parse(&body).into_output_errors();
+    errors.extend(e.iter().map(|e| diagnostic::relocate(e, &body, "callout", |offset| map.map(offset))));
+
+    Block::Callout(Callout {
+        kind,
+        title,
+        blocks: blocks.unwrap_or_default(),
+    })
+}
@@ -0,0 +1,32 @@
+use chumsky::{prelude::*, text::newline};
+
+use crate::parser::{line_terminator, Extra};
+
+use super::{ast::{Block, Definition, Inline}, nested};
+
+// terms on a line of their own, each followed by a `: ` line per definition
+pub(super) fn definitions<'src>(inline: Boxed<'src, 'src, &'src str, Vec<Inline>, Extra<'src>>) -> impl Parser<'src, &'src str, Block, Extra<'src>> + Clone {
+    let content = any()
+        .and_is(line_terminator().not())
+        .repeated()
+        .at_least(1)
+        .to_slice();
+    let term = nested(inline.clone(), content.clone()
+        .then_ignore(newline()
+            .then(just(": "))
+            .rewind()));
+    let definition = newline()
+        .ignore_then(just(": "))
+        .ignore_then(nested(inline, content));
+
+    term
+        .then(definition
+            .repeated()
+            .at_least(1)
+            .collect::<Vec<Vec<Inline>>>())
+        .map(|(term, definitions)| Definition { term, definitions })
+        .separated_by(newline())
+        .at_least(1)
+        .collect::<Vec<Definition>>()
+        .map(Block::Definitions)
+}
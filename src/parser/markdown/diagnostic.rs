@@ -0,0 +1,180 @@
+use std::ops::Range;
+
+use chumsky::{error::{Rich, RichPattern, RichReason}, input::Emitter, span::SimpleSpan};
+use thiserror::Error;
+
+// maps offsets in text produced by a rewriting pass back to the text it was given
+#[derive(Default)]
+pub(super) struct SourceMap(Vec<(usize, usize)>);
+
+impl SourceMap {
+    pub(super) fn anchor(&mut self, output: usize, input: usize) {
+        self.0.push((output, input));
+    }
+
+    pub(super) fn map(&self, offset: usize) -> usize {
+        match self.0.iter().rev().find(|(output, _)| *output <= offset) {
+            Some((output, input)) => input + (offset - output),
+            None => offset,
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+#[error("Failed to parse markdown: {}", .0.iter().map(|e| e.message.clone()).collect::<Vec<String>>().join("; "))]
+pub(super) struct SyntaxErrors(Vec<SyntaxError>);
+
+#[derive(Debug)]
+struct SyntaxError {
+    span: Range<usize>,
+    message: String,
+}
+
+impl SyntaxErrors {
+    pub(super) fn new<F: Fn(usize) -> usize>(errors: Vec<Rich<char>>, map: F) -> Self {
+        Self(errors
+            .into_iter()
+            .map(|e| {
+                let span = e.span().into_range();
+
+                SyntaxError {
+                    span: map(span.start)..map(span.end),
+                    message: message(&e, e.found().copied(), "input"),
+                }
+            })
+            .collect())
+    }
+
+    // points every error at its line and column in the original file, with the offending line underlined
+    pub(super) fn report(&self, path: &str, source: &str, offset: usize) -> String {
+        let mut report = format!("Failed to parse markdown in {}", path);
+
+        for e in &self.0 {
+            let start = (e.span.start + offset).min(source.len());
+            let end = (e.span.end + offset).clamp(start, source.len());
+            let line_start = source[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
+            let line_end = source[start..].find('\n').map(|i| start + i).unwrap_or(source.len());
+            let line = source[..start].matches('\n').count() + 1;
+            let column = source[line_start..start].chars().count() + 1;
+            let width = source[start..end.min(line_end)].chars().count().max(1);
+            let gutter = " ".repeat(line.to_string().len());
+
+            report.push_str(&format!(
+                "\n{}:{}:{}: {}\n{} |\n{} | {}\n{} | {}{}",
+                path, line, column, e.message,
+                gutter,
+                line, &source[line_start..line_end],
+                gutter, " ".repeat(column - 1), "^".repeat(width),
+            ));
+        }
+
+        report
+    }
+}
+
+// `found` is what `e` ran into, with the end of the text it was parsing from called the end of `end`
+fn message(e: &Rich<char>, found: Option<char>, end: &str) -> String {
+    let found = found.map_or_else(|| format!("end of {}", end), |c| format!("{:?}", c));
+    let mut expected = Vec::new();
+
+    for pattern in e.expected() {
+        let pattern = match pattern {
+            RichPattern::Token(c) => format!("{:?}", **c),
+            RichPattern::Label(label) => label.to_string(),
+            RichPattern::Identifier(identifier) => format!("{:?}", identifier),
+            RichPattern::EndOfInput => format!("end of {}", end),
+            RichPattern::Any | RichPattern::SomethingElse => continue,
+        };
+
+        if !expected.contains(&pattern) {
+            expected.push(pattern);
+        }
+    }
+
+    match e.reason() {
+        RichReason::Custom(message) => message.clone(),
+        RichReason::ExpectedFound { .. } if expected.is_empty() => format!("unexpected {}", found),
+        RichReason::ExpectedFound { .. } => format!("found {} but expected {}", found, expected.join(", ")),
+    }
+}
+
+// an error from parsing `text`, taken out of the input as part of a `what`, moved to where `map` says `text` is in the
+// input, and with the character it stopped at in `text` named even if the parser it came from saw no further
+pub(super) fn relocate<'a, F: Fn(usize) -> usize>(e: &Rich<char>, text: &str, what: &str, map: F) -> Rich<'a, char> {
+    let span = e.span().into_range();
+    let found = e.found().copied().or_else(|| text.get(span.start..).and_then(|rest| rest.chars().next()));
+
+    Rich::custom(SimpleSpan::from(map(span.start)..map(span.end)), message(e, found, what))
+}
+
+// errors from parsing `text`, a `what` found at `span`, reported where they are in the input
+pub(super) fn emit<'src>(errors: &[Rich<char>], text: &str, what: &str, span: SimpleSpan, emitter: &mut Emitter<Rich<'src, char>>) {
+    for e in errors {
+        emitter.emit(relocate(e, text, what, |offset| span.start + offset));
+    }
+}
+
+// joins lines borrowed from `source` with newlines, anchoring each one at where it is in `source`
+pub(super) fn join(lines: &[&str], source: &str) -> (String, SourceMap) {
+    let mut text = String::new();
+    let mut map = SourceMap::default();
+
+    for (i, line) in lines.iter().enumerate() {
+        if i > 0 {
+            text.push('\n');
+        }
+
+        map.anchor(text.len(), line.as_ptr() as usize - source.as_ptr() as usize);
+        text.push_str(line);
+    }
+
+    (text, map)
+}
+
+#[cfg(test)]
+mod tests {
+    use chumsky::{error::{LabelError, Rich, RichPattern}, span::SimpleSpan, util::MaybeRef};
+
+    use crate::parser::markdown::{diagnostic::{self, SyntaxErrors}, footnote, shortcode};
+
+    #[test]
+    fn report() {
+        let source = "---\ntitle: meow\n---\n\nfirst line\nsecond *line\n";
+        let offset = source.find("first").unwrap();
+        let errors = SyntaxErrors::new(vec![Rich::custom(SimpleSpan::from(18..23), "unclosed emphasis")], |o| o);
+        let res = errors.report("posts/meow.md", source, offset);
+        let expected = String::from("Failed to parse markdown in posts/meow.md\nposts/meow.md:6:8: unclosed emphasis\n  |\n6 | second *line\n  |        ^^^^^");
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn mapped_offsets() {
+        let body = "{{< note >}}\n[^a]: a footnote\nsecond *line";
+        let (text, _, shortcodes) = shortcode::extract(body).unwrap();
+        let (text, _, footnotes) = footnote::extract(&text);
+        let start = text.find('*').unwrap();
+        let errors = SyntaxErrors::new(vec![Rich::custom(SimpleSpan::from(start..start + 1), "meow")], |o| shortcodes.map(footnotes.map(o)));
+        let res = errors.report("meow.md", body, 0);
+        let expected = String::from("Failed to parse markdown in meow.md\nmeow.md:3:8: meow\n  |\n3 | second *line\n  |        ^");
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn expected() {
+        let expected = [
+            RichPattern::Token(MaybeRef::Val('"')),
+            RichPattern::Any,
+            RichPattern::Label("digit".into()),
+            RichPattern::Token(MaybeRef::Val('"')),
+            RichPattern::SomethingElse,
+        ];
+        let e = <Rich<char> as LabelError<&str, _>>::expected_found(expected, None, SimpleSpan::from(2..3));
+        let res = diagnostic::relocate(&e, "a \\ b", "formula", |o| o + 1);
+        let expected = Rich::custom(SimpleSpan::from(3..4), "found '\\\\' but expected '\"', digit");
+
+        assert_eq!(expected, res);
+        assert_eq!("found end of formula but expected '\"', digit", diagnostic::relocate(&e, "a ", "formula", |o| o).to_string());
+    }
+}
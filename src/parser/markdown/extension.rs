@@ -1,46 +1,82 @@
-use std::hash::{DefaultHasher, Hash, Hasher};
+use std::{fmt, hash::{DefaultHasher, Hash, Hasher}, sync::Arc};
 
 use chumsky::{prelude::*, text::newline};
 use rand::{rngs::StdRng, Rng, SeedableRng};
 
-use crate::parser::{fail, line_terminator};
+use crate::parser::{fail, line_terminator, Extra};
+
+use super::{ast::{Block, Inline}, nested};
 
 #[derive(Clone)]
 pub enum MarkdownExtension {
-    Inline(String, String, fn(String) -> String),
-    Block(String, fn(String) -> String, fn(Vec<String>) -> String),
+    Inline(String, String, Arc<dyn Fn(String) -> String>),
+    Block(String, Arc<dyn Fn(String) -> String>, Arc<dyn Fn(Vec<String>) -> String>),
 }
 
 impl MarkdownExtension {
-    pub fn inline<L: Into<String>, R: Into<String>>(left_delimiter: L, right_delimiter: R, wrapper: fn(String) -> String) -> MarkdownExtension {
-        MarkdownExtension::Inline(left_delimiter.into(), right_delimiter.into(), wrapper)
+    pub fn inline<L, R, W>(left_delimiter: L, right_delimiter: R, wrapper: W) -> MarkdownExtension
+    where
+        L: Into<String>,
+        R: Into<String>,
+        W: Fn(String) -> String + 'static,
+    {
+        MarkdownExtension::Inline(left_delimiter.into(), right_delimiter.into(), Arc::new(wrapper))
+    }
+
+    pub fn block<L, LW, BW>(line_start: L, line_wrapper: LW, block_wrapper: BW) -> MarkdownExtension
+    where
+        L: Into<String>,
+        LW: Fn(String) -> String + 'static,
+        BW: Fn(Vec<String>) -> String + 'static,
+    {
+        MarkdownExtension::Block(line_start.into(), Arc::new(line_wrapper), Arc::new(block_wrapper))
     }
+}
 
-    pub fn block<L: Into<String>>(line_start: L, line_wrapper: fn(String) -> String, block_wrapper: fn(Vec<String>) -> String) -> MarkdownExtension {
-        MarkdownExtension::Block(line_start.into(), line_wrapper, block_wrapper)
+// wrappers can't be printed, so only the syntax is shown
+impl fmt::Debug for MarkdownExtension {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MarkdownExtension::Inline(l, r, _) => f.debug_tuple("Inline").field(l).field(r).finish_non_exhaustive(),
+            MarkdownExtension::Block(line_start, _, _) => f.debug_tuple("Block").field(line_start).finish_non_exhaustive(),
+        }
+    }
+}
+
+// the same syntax with the same wrappers, so clones of one extension are equal
+impl PartialEq for MarkdownExtension {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (MarkdownExtension::Inline(l1, r1, w1), MarkdownExtension::Inline(l2, r2, w2)) => l1 == l2 && r1 == r2 && Arc::ptr_eq(w1, w2),
+            (MarkdownExtension::Block(s1, lw1, bw1), MarkdownExtension::Block(s2, lw2, bw2)) => {
+                s1 == s2 && Arc::ptr_eq(lw1, lw2) && Arc::ptr_eq(bw1, bw2)
+            },
+            _ => false,
+        }
     }
 }
 
 pub(crate) trait MarkdownExtensionList {
-    fn build_inline_parser<'src>(self, inline_parser: Boxed<'src, 'src, &'src str, String>) -> impl Parser<'src, &'src str, String>;
-    fn build_block_parser<'src>(self, inline_parser: Boxed<'src, 'src, &'src str, String>) -> impl Parser<'src, &'src str, String>;
+    fn build_inline_parser<'src>(self, inline_parser: Boxed<'src, 'src, &'src str, Vec<Inline>, Extra<'src>>) -> impl Parser<'src, &'src str, Inline, Extra<'src>>;
+    fn build_block_parser<'src>(self, inline_parser: Boxed<'src, 'src, &'src str, Vec<Inline>, Extra<'src>>) -> impl Parser<'src, &'src str, Block, Extra<'src>>;
 }
 
 impl MarkdownExtensionList for Vec<MarkdownExtension> {
-    fn build_inline_parser<'src>(self, inline_parser: Boxed<'src, 'src, &'src str, String>) -> impl Parser<'src, &'src str, String> {
+    fn build_inline_parser<'src>(self, inline_parser: Boxed<'src, 'src, &'src str, Vec<Inline>, Extra<'src>>) -> impl Parser<'src, &'src str, Inline, Extra<'src>> {
         self
             .iter()
-            .fold(fail().to(String::new()).boxed(), |previous, current| {
-                if let MarkdownExtension::Inline(l, r, wrapper) = current.clone() {
+            .fold(fail().to(Inline::SoftBreak).boxed(), |previous, current| {
+                if let MarkdownExtension::Inline(l, r, _) = current {
+                    let extension = current.clone();
+
                     previous.clone()
-                        .or(inline_parser.clone()
-                            .nested_in(any()
-                                .and_is(just(r.clone()).not())
-                                .repeated()
-                                .at_least(1)
-                                .to_slice()
-                                .delimited_by(just(l.clone()), just(r.clone())))
-                            .map(wrapper))
+                        .or(nested(inline_parser.clone(), any()
+                            .and_is(just(r.clone()).not())
+                            .repeated()
+                            .at_least(1)
+                            .to_slice()
+                            .delimited_by(just(l.clone()), just(r.clone())))
+                            .map(move |inner| Inline::Extension(extension.clone(), inner)))
                         .boxed()
                 } else {
                     previous
@@ -48,24 +84,29 @@ impl MarkdownExtensionList for Vec<MarkdownExtension> {
             })
     }
 
-    fn build_block_parser<'src>(self, inline_parser: Boxed<'src, 'src, &'src str, String>) -> impl Parser<'src, &'src str, String> {
+    fn build_block_parser<'src>(self, inline_parser: Boxed<'src, 'src, &'src str, Vec<Inline>, Extra<'src>>) -> impl Parser<'src, &'src str, Block, Extra<'src>> {
         self
             .iter()
-            .fold(fail().to(String::new()).boxed(), |previous, current| {
-                if let MarkdownExtension::Block(start, line_wrapper, block_wrapper) = current.clone() {
-                    previous.clone()
-                        .or(inline_parser.clone()
-                            .nested_in(just(start.clone())
-                                .ignore_then(any()
-                                    .and_is(line_terminator().not())
-                                    .repeated()
-                                    .at_least(1)
-                                    .to_slice()))
-                            .map(line_wrapper)
+            .fold(fail().to(Block::Rule).boxed(), |previous, current| {
+                // task items and definition lists have parsers of their own
+                if *current == tasks() || *current == definitions() {
+                    return previous;
+                }
+
+                if let MarkdownExtension::Block(start, _, _) = current {
+                    let extension = current.clone();
+
+                    previous
+                        .or(just(start.clone())
+                            .then(nested(inline_parser.clone(), any()
+                                .and_is(line_terminator().not())
+                                .repeated()
+                                .at_least(1)
+                                .to_slice()))
                             .separated_by(newline())
                             .at_least(1)
-                            .collect::<Vec<String>>()
-                            .map(block_wrapper))
+                            .collect::<Vec<(String, Vec<Inline>)>>()
+                            .map(move |lines| Block::Extension(extension.clone(), lines)))
                         .boxed()
                 } else {
                     previous
@@ -74,6 +115,17 @@ impl MarkdownExtensionList for Vec<MarkdownExtension> {
     }
 }
 
+thread_local! {
+    // the same extensions every time, so the parser can tell whether they've been added
+    static TASKS: MarkdownExtension = MarkdownExtension::block("- [ ] ", |s| s, |lines| lines.concat());
+    static DEFINITIONS: MarkdownExtension = MarkdownExtension::block(": ", |s| s, |lines| lines.concat());
+}
+
+// list items starting with `[ ]` or `[x]` become tasks with a checkbox
+pub fn tasks() -> MarkdownExtension {
+    TASKS.with(MarkdownExtension::clone)
+}
+
 pub fn small() -> MarkdownExtension {
     MarkdownExtension::block(
         "-# ",
@@ -82,11 +134,16 @@ pub fn small() -> MarkdownExtension {
     )
 }
 
+// terms on a line of their own, each followed by `: ` definitions
+pub fn definitions() -> MarkdownExtension {
+    DEFINITIONS.with(MarkdownExtension::clone)
+}
+
 pub fn quote() -> MarkdownExtension {
     MarkdownExtension::block(
         "> ",
         |s| s,
-        |lines| format!("</p><blockquote>{}</blockquote><p>", lines.join("<br/>"))
+        |lines| format!("<blockquote>{}</blockquote>", lines.join("<br/>"))
     )
 }
 
@@ -123,7 +180,7 @@ pub fn wobbly() -> MarkdownExtension {
 mod tests {
     use chumsky::Parser;
 
-    use crate::parser::markdown::{extension, make_parser};
+    use crate::parser::markdown::{extension::{self, MarkdownExtension}, make_parser};
 
     #[test]
     fn small() {
@@ -143,6 +200,34 @@ mod tests {
         assert_eq!(expected, res)
     }
 
+    #[test]
+    fn definitions() {
+        let p = make_parser(&vec![extension::definitions()]);
+        let res = p.parse("meow\n\nmrrp\n: a *cat* sound\n: a greeting\nnya\n: also one\n\npurr: hiss").into_result().unwrap();
+        let expected = format!("<p>meow</p><dl><dt>mrrp</dt><dd>a <i>cat</i> sound</dd><dd>a greeting</dd><dt>nya</dt><dd>also one</dd></dl><p>purr: hiss</p>");
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn definitions_disabled() {
+        let p = make_parser(&vec![extension::quote()]);
+        let res = p.parse("mrrp\n: a *cat* sound").into_result().unwrap();
+        let expected = format!("mrrp\n: a <i>cat</i> sound");
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn capturing() {
+        let class = String::from("meow");
+        let p = make_parser(&vec![MarkdownExtension::inline("{{", "}}", move |s| format!("<span class=\"{}\">{}</span>", class, s))]);
+        let res = p.parse("{{mrrp}}").into_result().unwrap();
+        let expected = format!("<span class=\"meow\">mrrp</span>");
+
+        assert_eq!(expected, res);
+    }
+
     #[test]
     fn wobbly() {
         let p = make_parser(&vec![extension::wobbly()]);
@@ -0,0 +1,166 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use anyhow::{anyhow, Result};
+use chumsky::prelude::*;
+use regex::Regex;
+
+use crate::parser::Extra;
+
+use super::{ast::{self, Block, Inline}, diagnostic::{self, SourceMap}};
+
+static DEFINITION: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\[\^([^\]\s]+)\]:[ \t]?(.*)$").unwrap());
+static RENDERED: LazyLock<Regex> = LazyLock::new(|| Regex::new("<sup class=\"footnote-ref\" id=\"fnref-[0-9-]+\"><a href=\"#fn-[0-9]+\">[0-9]+</a></sup>").unwrap());
+
+pub(super) fn reference<'src>() -> impl Parser<'src, &'src str, Inline, Extra<'src>> + Clone {
+    none_of("] \t\r\n")
+        .repeated()
+        .at_least(1)
+        .to_slice()
+        .delimited_by(just("[^"), just(']'))
+        .map(|name: &str| Inline::FootnoteReference(name.to_owned(), None))
+}
+
+pub(super) struct Definition {
+    pub(super) text: String,
+    // offsets in `text` back to the body it was taken out of
+    pub(super) map: SourceMap,
+}
+
+// removes `[^name]: text` definitions (and their indented continuation lines) from the body
+pub(super) fn extract(body: &str) -> (String, HashMap<String, Definition>, SourceMap) {
+    let lines = body.lines().collect::<Vec<&str>>();
+    let mut text = String::new();
+    let mut map = SourceMap::default();
+    let mut definitions = HashMap::new();
+    let mut fenced = false;
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+        }
+
+        match DEFINITION.captures(line) {
+            Some(captures) if !fenced => {
+                let name = captures[1].to_owned();
+                let mut content = vec![&line[captures.get(2).unwrap().start()..]];
+                i += 1;
+
+                while i < lines.len() {
+                    if let Some(l) = continuation(lines[i]) {
+                        content.push(l);
+                    } else if lines[i].trim().is_empty() && lines[i..].iter().find(|l| !l.trim().is_empty()).is_some_and(|l| continuation(l).is_some()) {
+                        content.push(&lines[i][..0]);
+                    } else {
+                        break;
+                    }
+
+                    i += 1;
+                }
+
+                // left untrimmed, `MarkdownParser::blocks` trims it while keeping track of the offsets
+                let (text, map) = diagnostic::join(&content, body);
+                definitions.insert(name, Definition { text, map });
+            },
+            _ => {
+                if !text.is_empty() || i > 0 {
+                    text.push('\n');
+                }
+
+                map.anchor(text.len(), line.as_ptr() as usize - body.as_ptr() as usize);
+                text.push_str(line);
+                i += 1;
+            },
+        }
+    }
+
+    (text, definitions, map)
+}
+
+// an indented line that carries on the definition before it
+fn continuation(line: &str) -> Option<&str> {
+    line.strip_prefix("    ").or_else(|| line.strip_prefix('\t'))
+}
+
+// the definitions in a document, and the numbers its references have been given so far
+#[derive(Default)]
+pub(super) struct Footnotes {
+    definitions: HashMap<String, Vec<Block>>,
+    // names, in the order they were first referenced
+    order: Vec<String>,
+    counts: HashMap<String, usize>,
+}
+
+impl Footnotes {
+    pub(super) fn define(&mut self, name: String, blocks: Vec<Block>) {
+        self.definitions.insert(name, blocks);
+    }
+
+    // numbers the references in `blocks` in order of first use, carrying on from the ones before
+    pub(super) fn number(&mut self, blocks: &mut [Block]) {
+        ast::visit_inlines(blocks, &mut |inline| if let Inline::FootnoteReference(name, numbered) = inline {
+            let number = match self.order.iter().position(|n| n == name) {
+                Some(i) => i + 1,
+                None => {
+                    self.order.push(name.clone());
+                    self.order.len()
+                },
+            };
+            let count = self.counts.entry(name.clone()).or_insert(0);
+            *count += 1;
+
+            *numbered = Some((number, *count));
+        });
+    }
+
+    // the footnote section, if anything was referenced
+    pub(super) fn section(mut self) -> Result<String> {
+        let mut items = String::new();
+        let mut i = 0;
+
+        // definitions can reference further footnotes, which get numbered after the ones in the body
+        while i < self.order.len() {
+            let name = self.order[i].clone();
+            let mut blocks = self.definitions
+                .get(&name)
+                .ok_or_else(|| anyhow!("Footnote [^{}] is referenced but never defined", name))?
+                .clone();
+            self.number(&mut blocks);
+
+            let inner = ast::html(&blocks);
+            let backrefs = (1..=self.counts[&name])
+                .map(|n| format!("<a href=\"#{}\" class=\"footnote-backref\">↩</a>", ref_id(i + 1, n)))
+                .collect::<Vec<String>>()
+                .join(" ");
+
+            let inner = match inner.strip_suffix("</p>") {
+                Some(inner) => format!("{} {}</p>", inner, backrefs),
+                None => format!("{}{}", inner, backrefs),
+            };
+
+            items.push_str(&format!("<li id=\"fn-{}\">{}</li>", i + 1, inner));
+            i += 1;
+        }
+
+        if items.is_empty() {
+            return Ok(items);
+        }
+
+        Ok(format!("<section class=\"footnotes\"><ol>{}</ol></section>", items))
+    }
+}
+
+// drops the references rendered into shortcodes from html that gets no footnote section, like the summary
+pub(super) fn strip(html: &str) -> String {
+    RENDERED.replace_all(html, "").into_owned()
+}
+
+pub(super) fn ref_id(number: usize, count: usize) -> String {
+    if count == 1 {
+        format!("fnref-{}", number)
+    } else {
+        format!("fnref-{}-{}", number, count)
+    }
+}
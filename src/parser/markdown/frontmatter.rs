@@ -0,0 +1,173 @@
+use std::collections::HashMap;
+
+use fronma::parser::parse;
+use tera::Value;
+
+use crate::error::FrontmatterError;
+
+// `---` yaml, `+++` toml, or a leading json object, bare or fenced by `---json`, and the body after it
+pub(super) fn split(text: &str) -> Result<(HashMap<String, Value>, &str), FrontmatterError> {
+    if let Some((frontmatter, body)) = fenced(text, "---json", "---") {
+        return Ok((serde_json::from_str(frontmatter.ok_or(FrontmatterError::MissingJsonEnd)?)?, body));
+    }
+
+    // an opening brace alone could just as well start a shortcode
+    if text.strip_prefix('{').is_some_and(|rest| rest.trim_start().starts_with(['"', '}'])) {
+        let mut stream = serde_json::Deserializer::from_str(text).into_iter::<HashMap<String, Value>>();
+
+        return match stream.next() {
+            Some(Ok(headers)) => Ok((headers, &text[stream.byte_offset()..])),
+            Some(Err(e)) if e.is_eof() => Err(FrontmatterError::MissingJsonEnd),
+            Some(Err(e)) => Err(e.into()),
+            None => Err(FrontmatterError::MissingJsonEnd),
+        };
+    }
+
+    if let Some((frontmatter, body)) = fenced(text, "+++", "+++") {
+        let frontmatter = frontmatter.ok_or(FrontmatterError::MissingTomlEnd)?;
+        let table = frontmatter.parse::<toml::Table>().map_err(|e| {
+            let message = e.message().trim_end().replace('\n', ", ");
+
+            // counting from the opening `+++`
+            FrontmatterError::Toml(match e.span() {
+                Some(span) => format!("{} at line {}", message, frontmatter[..span.start].matches('\n').count() + 2),
+                None => message,
+            })
+        })?;
+
+        return Ok((table.into_iter().map(|(key, value)| (key, json(value))).collect(), body));
+    }
+
+    match parse::<HashMap<String, Value>>(text) {
+        Ok(val) => Ok((val.headers, val.body)),
+        Err(e) => match e {
+            fronma::error::Error::MissingBeginningLine => Ok((HashMap::new(), text)),
+            fronma::error::Error::MissingEndingLine => Err(FrontmatterError::MissingYamlEnd),
+            fronma::error::Error::SerdeYaml(e) => Err(FrontmatterError::Yaml(e.to_string())),
+        },
+    }
+}
+
+// the text between an `open` line at the very start and the next `close` line, and the body after it,
+// or none if the text doesn't start with `open` and no frontmatter if `close` never comes
+fn fenced<'a>(text: &'a str, open: &str, close: &str) -> Option<(Option<&'a str>, &'a str)> {
+    let first = text.split_inclusive('\n').next()?;
+
+    if first.trim_end_matches(['\r', '\n']) != open {
+        return None;
+    }
+
+    let start = first.len();
+    let mut end = start;
+
+    for line in text[start..].split_inclusive('\n') {
+        if line.trim_end() == close {
+            return Some((Some(&text[start..end]), &text[end + line.len()..]));
+        }
+
+        end += line.len();
+    }
+
+    Some((None, text))
+}
+
+// dates, which json has no type for, become strings
+fn json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(s) => Value::String(s),
+        toml::Value::Integer(i) => Value::from(i),
+        toml::Value::Float(f) => Value::from(f),
+        toml::Value::Boolean(b) => Value::Bool(b),
+        toml::Value::Datetime(d) => Value::String(d.to_string()),
+        toml::Value::Array(array) => Value::Array(array.into_iter().map(json).collect()),
+        toml::Value::Table(table) => Value::Object(table.into_iter().map(|(key, value)| (key, json(value))).collect()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::error::FrontmatterError;
+
+    use super::split;
+
+    #[test]
+    fn yaml() {
+        let (headers, body) = split("---\ntitle: meow\n---\nmrrp").unwrap();
+
+        assert_eq!(json!("meow"), headers["title"]);
+        assert_eq!("mrrp", body);
+    }
+
+    #[test]
+    fn toml() {
+        let text = "+++\ntitle = \"meow\" # a comment\ntags = [\n  'mrrp',\n  \"nya\\u00e9\", # trailing\n]\ndate = 2024-01-02T03:04:05Z\ncount = 1_000\nratio = -0.5\nhex = 0xff\ndraft = false\nsite.name = \"\"\"\npurr \\\n  hiss\"\"\"\nauthor = { name = \"cat\", age = 3 }\n\n[extra]\nkind = 'tabby'\n\n[[paws]]\nside = \"left\"\n[[paws]]\nside = \"right\"\n+++\nmeow";
+        let (headers, body) = split(text).unwrap();
+
+        assert_eq!(json!("meow"), headers["title"]);
+        assert_eq!(json!(["mrrp", "nyaé"]), headers["tags"]);
+        assert_eq!(json!("2024-01-02T03:04:05Z"), headers["date"]);
+        assert_eq!(json!(1000), headers["count"]);
+        assert_eq!(json!(-0.5), headers["ratio"]);
+        assert_eq!(json!(255), headers["hex"]);
+        assert_eq!(json!(false), headers["draft"]);
+        assert_eq!(json!({ "name": "purr hiss" }), headers["site"]);
+        assert_eq!(json!({ "name": "cat", "age": 3 }), headers["author"]);
+        assert_eq!(json!({ "kind": "tabby" }), headers["extra"]);
+        assert_eq!(json!([{ "side": "left" }, { "side": "right" }]), headers["paws"]);
+        assert_eq!("meow", body);
+    }
+
+    #[test]
+    fn json() {
+        let (headers, body) = split("---json\n{\n  \"title\": \"meow\",\n  \"tags\": [\"mrrp\"]\n}\n---\nnya").unwrap();
+
+        assert_eq!(json!("meow"), headers["title"]);
+        assert_eq!(json!(["mrrp"]), headers["tags"]);
+        assert_eq!("nya", body);
+    }
+
+    #[test]
+    fn bare_json() {
+        let (headers, body) = split("{\n  \"title\": \"meow\",\n  \"tags\": [\"mrrp\"]\n}\nnya").unwrap();
+
+        assert_eq!(json!("meow"), headers["title"]);
+        assert_eq!(json!(["mrrp"]), headers["tags"]);
+        assert_eq!("\nnya", body);
+
+        let (headers, body) = split("{}\nmeow").unwrap();
+
+        assert!(headers.is_empty());
+        assert_eq!("\nmeow", body);
+    }
+
+    #[test]
+    fn none() {
+        let (headers, body) = split("{{< figure >}}\nmeow").unwrap();
+
+        assert!(headers.is_empty());
+        assert_eq!("{{< figure >}}\nmeow", body);
+    }
+
+    #[test]
+    fn missing_ends() {
+        assert!(matches!(split("---\ntitle: meow\n"), Err(FrontmatterError::MissingYamlEnd)));
+        assert!(matches!(split("+++\ntitle = \"meow\"\n"), Err(FrontmatterError::MissingTomlEnd)));
+        assert!(matches!(split("---json\n{ \"title\": \"meow\" }\n"), Err(FrontmatterError::MissingJsonEnd)));
+        assert!(matches!(split("{ \"title\": \"meow\"\n"), Err(FrontmatterError::MissingJsonEnd)));
+    }
+
+    #[test]
+    fn invalid() {
+        assert!(matches!(split("---\ntitle: [meow\n---\n"), Err(FrontmatterError::Yaml(_))));
+        assert!(matches!(split("---json\n{ \"title\": meow }\n---\n"), Err(FrontmatterError::Json(_))));
+        assert!(matches!(split("{ \"title\": meow }\n"), Err(FrontmatterError::Json(_))));
+
+        let error = split("+++\ntitle = \"meow\"\ntitle = \"mrrp\"\n+++\n").unwrap_err();
+        assert_eq!("Failed to parse TOML frontmatter: duplicate key `title` in document root at line 3", error.to_string());
+
+        let error = split("+++\ntitle = \"meow\"\ncount = nya\n+++\n").unwrap_err();
+        assert_eq!("Failed to parse TOML frontmatter: invalid string, expected `\"`, `'` at line 3", error.to_string());
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::HashSet;
+
+use tera::{Map, Value};
+
+use super::ast::{self, Block, Inline};
+
+struct Heading {
+    level: usize,
+    id: String,
+    title: String,
+}
+
+// the ids given out so far in a document, and its table of contents
+pub(super) struct Headings {
+    anchors: bool,
+    used: HashSet<String>,
+    headings: Vec<Heading>,
+}
+
+impl Headings {
+    pub(super) fn new(anchors: bool) -> Self {
+        Self {
+            anchors,
+            used: HashSet::new(),
+            headings: Vec::new(),
+        }
+    }
+
+    // gives every heading in `blocks` an id unique in the document, adding it to the table of contents
+    pub(super) fn anchor(&mut self, blocks: &mut [Block]) {
+        ast::visit_blocks(blocks, &mut |block| if let Block::Heading(level, id, inlines) = block {
+            let title = ast::inline_text(inlines).trim().to_owned();
+            let unique = unique_id(&slugify(&title), &mut self.used);
+
+            if self.anchors {
+                inlines.push(Inline::Html(format!(" <a class=\"anchor\" href=\"#{}\" aria-hidden=\"true\">#</a>", unique)));
+            }
+
+            self.headings.push(Heading { level: *level, id: unique.clone(), title });
+            *id = Some(unique);
+        });
+    }
+
+    pub(super) fn toc(&self) -> Value {
+        Value::Array(tree(&self.headings))
+    }
+}
+
+fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+
+    for c in text.trim().to_lowercase().chars() {
+        if c.is_alphanumeric() || c == '-' || c == '_' {
+            slug.push(c);
+        } else if c.is_whitespace() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    let slug = slug.trim_matches('-');
+
+    if slug.is_empty() {
+        "section".to_owned()
+    } else {
+        slug.to_owned()
+    }
+}
+
+fn unique_id(slug: &str, used: &mut HashSet<String>) -> String {
+    let mut id = slug.to_owned();
+    let mut n = 0;
+
+    while used.contains(&id) {
+        n += 1;
+        id = format!("{}-{}", slug, n);
+    }
+
+    used.insert(id.clone());
+
+    id
+}
+
+// nests each heading under the closest preceding heading of a higher level
+fn tree(headings: &[Heading]) -> Vec<Value> {
+    let mut nodes = Vec::new();
+    let mut i = 0;
+
+    while i < headings.len() {
+        let heading = &headings[i];
+        let end = headings[i + 1..]
+            .iter()
+            .position(|h| h.level <= heading.level)
+            .map(|p| i + 1 + p)
+            .unwrap_or(headings.len());
+
+        let mut node = Map::new();
+        node.insert("level".to_owned(), Value::from(heading.level));
+        node.insert("id".to_owned(), Value::from(heading.id.clone()));
+        node.insert("title".to_owned(), Value::from(heading.title.clone()));
+        node.insert("children".to_owned(), Value::Array(tree(&headings[i + 1..end])));
+        nodes.push(Value::Object(node));
+
+        i = end;
+    }
+
+    nodes
+}
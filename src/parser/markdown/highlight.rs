@@ -0,0 +1,72 @@
+use std::sync::{LazyLock, OnceLock};
+
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use syntect::{easy::HighlightLines, highlighting::ThemeSet, html::{styled_line_to_highlighted_html, ClassStyle, ClassedHTMLGenerator, IncludeBackground}, parsing::SyntaxSet, util::LinesWithEndings};
+
+static SYNTAXES: OnceLock<SyntaxSet> = OnceLock::new();
+static THEMES: OnceLock<ThemeSet> = OnceLock::new();
+static CODE_BLOCK: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?s)<pre>(<small>[^<]*</small>)?<code class="language-([^"]*)">(.*?)</code></pre>"#).unwrap());
+
+#[derive(Clone)]
+pub(super) enum Highlighting {
+    Inline(String),
+    Classed,
+}
+
+// tokenizes fenced code blocks of known languages, leaving the rest untouched
+pub(super) fn highlight(html: &str, highlighting: &Highlighting) -> Result<String> {
+    let syntaxes = SYNTAXES.get_or_init(SyntaxSet::load_defaults_newlines);
+    let mut res = String::new();
+    let mut last = 0;
+
+    for captures in CODE_BLOCK.captures_iter(html) {
+        let whole = captures.get(0).unwrap();
+        let Some(syntax) = syntaxes.find_syntax_by_token(&captures[2]) else {
+            continue;
+        };
+        let code = html_escape::decode_html_entities(&captures[3]);
+        let caption = captures.get(1).map(|m| m.as_str()).unwrap_or("");
+
+        let (pre, inner) = match highlighting {
+            Highlighting::Inline(name) => {
+                let theme = THEMES
+                    .get_or_init(ThemeSet::load_defaults)
+                    .themes
+                    .get(name)
+                    .ok_or_else(|| anyhow!("Unknown highlighting theme '{}'", name))?;
+                let mut highlighter = HighlightLines::new(syntax, theme);
+                let mut inner = String::new();
+
+                for line in LinesWithEndings::from(&code) {
+                    let regions = highlighter.highlight_line(line, syntaxes)?;
+                    inner.push_str(&styled_line_to_highlighted_html(&regions, IncludeBackground::No)?);
+                }
+
+                let pre = match theme.settings.background {
+                    Some(c) => format!("<pre style=\"background-color: #{:02x}{:02x}{:02x};\">", c.r, c.g, c.b),
+                    None => "<pre>".to_owned(),
+                };
+
+                (pre, inner)
+            },
+            Highlighting::Classed => {
+                let mut generator = ClassedHTMLGenerator::new_with_class_style(syntax, syntaxes, ClassStyle::Spaced);
+
+                for line in LinesWithEndings::from(&code) {
+                    generator.parse_html_for_line_which_includes_newline(line)?;
+                }
+
+                ("<pre>".to_owned(), generator.finalize())
+            },
+        };
+
+        res.push_str(&html[last..whole.start()]);
+        res.push_str(&format!("{}{}<code class=\"language-{}\">{}</code></pre>", pre, caption, &captures[2], inner));
+        last = whole.end();
+    }
+
+    res.push_str(&html[last..]);
+
+    Ok(res)
+}
@@ -0,0 +1,398 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use chumsky::{prelude::*, text::newline};
+use regex::{Captures, Regex};
+
+use crate::parser::Extra;
+
+use super::{ast::{self, Block, Inline}, summary};
+
+// tags that start a block of raw html when they open a line, as in CommonMark
+static BLOCK_TAGS: &[&str] = &[
+    "address", "article", "aside", "audio", "blockquote", "body", "canvas", "center", "details", "dialog", "dd", "div", "dl", "dt",
+    "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "head", "header", "hr", "html", "iframe",
+    "li", "main", "menu", "nav", "noscript", "ol", "p", "picture", "pre", "script", "section", "source", "style", "summary", "table",
+    "tbody", "td", "template", "textarea", "tfoot", "th", "thead", "tr", "ul", "video",
+];
+
+// attributes holding a url, which have their scheme checked when sanitizing
+static URL_ATTRIBUTES: &[&str] = &["href", "src", "cite"];
+
+// tags whose content is dropped along with them when they aren't allowed
+static DROPPED_CONTENT: &[&str] = &["script", "style"];
+
+// tags that are never closed, so never left open either
+static VOID_TAGS: &[&str] = &["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr"];
+
+static TOKEN: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"(?s)<!--(.*?)-->|<(/?)([a-zA-Z][a-zA-Z0-9-]*)((?:\s+[^\s"'>/=]+(?:\s*=\s*(?:"[^"]*"|'[^']*'|[^\s"'=<>`]+))?)*)\s*(/?)>"#).unwrap()
+});
+static ATTRIBUTE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"([^\s"'>/=]+)(?:\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s"'=<>`]+)))?"#).unwrap());
+
+// what happens to html written in the markdown source
+#[derive(Clone)]
+pub enum RawHtml {
+    // passed through untouched
+    Verbatim,
+    // shown as text
+    Escape,
+    // only allowed tags and attributes are kept, along with markdown links and images to allowed schemes
+    Sanitize(Allowlist),
+}
+
+#[derive(Clone)]
+pub struct Allowlist {
+    tags: HashMap<String, Vec<String>>,
+    schemes: Vec<String>,
+}
+
+impl Allowlist {
+    // allows nothing, to be built up with `tag` and `scheme`
+    pub fn empty() -> Self {
+        Self {
+            tags: HashMap::new(),
+            schemes: Vec::new(),
+        }
+    }
+
+    pub fn tag(mut self, name: &str, attributes: &[&str]) -> Self {
+        self.tags
+            .entry(name.to_lowercase())
+            .or_default()
+            .extend(attributes.iter().map(|attribute| attribute.to_lowercase()));
+
+        self
+    }
+
+    pub fn scheme(mut self, scheme: &str) -> Self {
+        self.schemes.push(scheme.to_lowercase());
+
+        self
+    }
+
+    // relative urls are always allowed, absolute ones need an allowed scheme
+    fn allows_url(&self, url: &str) -> bool {
+        let url = html_escape::decode_html_entities(url)
+            .chars()
+            .filter(|c| !c.is_whitespace() && !c.is_control())
+            .collect::<String>()
+            .to_lowercase();
+
+        match url.split_once(':') {
+            Some((scheme, _)) if !scheme.contains(['/', '?', '#']) => self.schemes.iter().any(|allowed| allowed == scheme),
+            _ => true,
+        }
+    }
+
+    // `open` is the allowed tags left open so far in the fragment this html is part of, any closing tag not in it is
+    // dropped so the html can't close what it's rendered inside of
+    fn clean(&self, html: &str, open: &mut Vec<String>) -> String {
+        let mut cleaned = String::new();
+        let mut last = 0;
+        let mut dropping: Option<String> = None;
+
+        for captures in TOKEN.captures_iter(html) {
+            let whole = captures.get(0).unwrap();
+            let name = captures.get(3).map(|name| name.as_str().to_lowercase());
+            let closing = captures.get(2).is_some_and(|slash| !slash.is_empty());
+
+            if let Some(dropped) = &dropping {
+                if closing && name.as_ref() == Some(dropped) {
+                    dropping = None;
+                }
+
+                last = whole.end();
+                continue;
+            }
+
+            cleaned.push_str(&escape(&html[last..whole.start()]));
+            last = whole.end();
+
+            let Some(name) = name else {
+                cleaned.push_str(&comment(&captures));
+                continue;
+            };
+            let Some(allowed) = self.tags.get(&name) else {
+                if !closing && DROPPED_CONTENT.contains(&name.as_str()) {
+                    dropping = Some(name);
+                }
+
+                continue;
+            };
+
+            if closing {
+                if let Some(i) = open.iter().rposition(|tag| *tag == name) {
+                    cleaned.push_str(&close(open.split_off(i)));
+                }
+
+                continue;
+            }
+
+            cleaned.push_str(&format!("<{}", name));
+
+            for attribute in ATTRIBUTE.captures_iter(&captures[4]) {
+                let key = attribute[1].to_lowercase();
+                let value = attribute.get(2)
+                    .or(attribute.get(3))
+                    .or(attribute.get(4))
+                    .map(|value| value.as_str())
+                    .unwrap_or_default();
+
+                if !allowed.contains(&key) || (URL_ATTRIBUTES.contains(&key.as_str()) && !self.allows_url(value)) {
+                    continue;
+                }
+
+                cleaned.push_str(&format!(" {}=\"{}\"", key, html_escape::encode_double_quoted_attribute(&html_escape::decode_html_entities(value))));
+            }
+
+            cleaned.push_str(&format!("{}>", &captures[5]));
+
+            if captures[5].is_empty() && !VOID_TAGS.contains(&name.as_str()) {
+                open.push(name);
+            }
+        }
+
+        if dropping.is_none() {
+            cleaned.push_str(&escape(&html[last..]));
+        }
+
+        cleaned
+    }
+}
+
+impl Default for Allowlist {
+    // formatting, structure, tables, links and images, with http(s) and mailto urls
+    fn default() -> Self {
+        [
+            "abbr", "b", "br", "code", "dd", "del", "details", "div", "dl", "dt", "em", "figcaption", "figure", "h1", "h2", "h3", "h4",
+            "h5", "h6", "hr", "i", "ins", "kbd", "li", "mark", "p", "pre", "s", "samp", "small", "span", "strong", "sub", "summary", "sup",
+            "table", "tbody", "tfoot", "thead", "tr", "u", "ul", "var",
+        ]
+            .iter()
+            .fold(Self::empty(), |allowlist, tag| allowlist.tag(tag, &[]))
+            .tag("a", &["href", "title"])
+            .tag("abbr", &["title"])
+            .tag("blockquote", &["cite"])
+            .tag("img", &["src", "alt", "title", "width", "height"])
+            .tag("ol", &["start"])
+            .tag("q", &["cite"])
+            .tag("td", &["colspan", "rowspan"])
+            .tag("th", &["colspan", "rowspan"])
+            .scheme("http")
+            .scheme("https")
+            .scheme("mailto")
+    }
+}
+
+// closing tags for `open`, innermost first
+fn close(open: Vec<String>) -> String {
+    open
+        .iter()
+        .rev()
+        .map(|tag| format!("</{}>", tag))
+        .collect::<Vec<String>>()
+        .concat()
+}
+
+// a comment is only kept if nothing in it could end it early and let markup through
+fn comment(captures: &Captures) -> String {
+    let inner = &captures[1];
+
+    if inner.contains("--") || inner.starts_with('>') || inner.starts_with("->") {
+        String::new()
+    } else {
+        captures[0].to_owned()
+    }
+}
+
+// entities written in the source are left alone, any other `&` is escaped
+pub(super) fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for (i, c) in text.char_indices() {
+        match c {
+            '&' if !is_entity(&text[i + 1..]) => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+// whether the text after an `&` makes it a named, decimal or hex entity
+fn is_entity(text: &str) -> bool {
+    let end = text.find(|c: char| !c.is_ascii_alphanumeric() && c != '#').unwrap_or(text.len());
+    let entity = &text[..end];
+
+    if !text[end..].starts_with(';') {
+        return false;
+    }
+
+    match entity.strip_prefix('#') {
+        Some(hex) if hex.starts_with(['x', 'X']) => (2..=7).contains(&hex.len()) && hex[1..].chars().all(|c| c.is_ascii_hexdigit()),
+        Some(decimal) => (1..=7).contains(&decimal.len()) && decimal.chars().all(|c| c.is_ascii_digit()),
+        None => entity.starts_with(|c: char| c.is_ascii_alphabetic()) && !entity.contains('#'),
+    }
+}
+
+// the only comment let through unescaped, browsers end comments in more ways than `-->` so any other could hide markup
+fn is_more_marker(html: &str) -> bool {
+    html.trim() == summary::MORE
+}
+
+fn name<'src>() -> impl Parser<'src, &'src str, &'src str, Extra<'src>> + Clone {
+    any()
+        .filter(char::is_ascii_alphabetic)
+        .then(any()
+            .filter(|c: &char| c.is_ascii_alphanumeric() || *c == '-')
+            .repeated())
+        .to_slice()
+}
+
+// an opening or closing tag, or a comment
+pub(super) fn inline<'src>() -> impl Parser<'src, &'src str, Inline, Extra<'src>> + Clone {
+    let space = one_of(" \t\n").repeated();
+    let value = choice((
+        none_of("\"")
+            .repeated()
+            .delimited_by(just('"'), just('"')),
+        none_of("'")
+            .repeated()
+            .delimited_by(just('\''), just('\'')),
+        none_of(" \t\n\"'=<>`")
+            .repeated()
+            .at_least(1),
+    ));
+    let attribute = one_of(" \t\n")
+        .repeated()
+        .at_least(1)
+        .then(any()
+            .filter(|c: &char| c.is_ascii_alphabetic() || "_:".contains(*c))
+            .then(any()
+                .filter(|c: &char| c.is_ascii_alphanumeric() || "_.:-".contains(*c))
+                .repeated()))
+        .then(space
+            .then(just('='))
+            .then(space)
+            .then(value)
+            .or_not());
+
+    choice((
+        just('<')
+            .then(name())
+            .then(attribute.repeated())
+            .then(space)
+            .then(just('/').or_not())
+            .then(just('>'))
+            .ignored(),
+        just("</")
+            .then(name())
+            .then(space)
+            .then(just('>'))
+            .ignored(),
+        just("<!--")
+            .then(any()
+                .and_is(just("-->").not())
+                .repeated())
+            .then(just("-->"))
+            .ignored(),
+    ))
+        .to_slice()
+        .map(|html: &str| Inline::Html(html.to_owned()))
+}
+
+// a line opening with a block-level tag, running until the next blank line
+pub(super) fn block<'src>() -> impl Parser<'src, &'src str, Block, Extra<'src>> + Clone {
+    let line = any()
+        .and_is(newline().not())
+        .repeated();
+    let blank = one_of(" \t")
+        .repeated()
+        .then(newline().or(end()));
+
+    just('<')
+        .then(just('/').or_not())
+        .then(name().filter(|name: &&str| BLOCK_TAGS.contains(&name.to_lowercase().as_str())))
+        .then(choice((
+            one_of(" \t/>")
+                .ignored(),
+            newline(),
+            end(),
+        )).rewind())
+        .then(line)
+        .then(newline()
+            .then(blank.not())
+            .then(line)
+            .repeated())
+        .to_slice()
+        .map(|html: &str| Block::Html(html.trim_end().to_owned()))
+}
+
+pub(super) fn apply(blocks: &mut [Block], policy: &RawHtml) {
+    match policy {
+        RawHtml::Verbatim => {},
+        RawHtml::Escape => {
+            ast::visit_blocks(blocks, &mut |block| {
+                if let Block::Html(html) = block {
+                    if !is_more_marker(html) {
+                        *block = Block::Paragraph(vec![Inline::Text(html.clone())]);
+                    }
+                }
+            });
+            ast::visit_inlines(blocks, &mut |inline| {
+                if let Inline::Html(html) = inline {
+                    if !is_more_marker(html) {
+                        *inline = Inline::Text(html.clone());
+                    }
+                }
+            });
+        },
+        RawHtml::Sanitize(allowlist) => {
+            ast::visit_blocks(blocks, &mut |block| {
+                if let Block::Html(html) = block {
+                    let mut open = Vec::new();
+                    let cleaned = allowlist.clean(html, &mut open);
+
+                    *html = cleaned + &close(open);
+                }
+            });
+            ast::visit_block_inlines(blocks, &mut |inlines| sanitize(inlines, allowlist));
+        },
+    }
+}
+
+// links and images to schemes that aren't allowed are left as their text, and tags are closed within the inlines
+// they're opened in, so they can't reach outside of a link or emphasis
+fn sanitize(inlines: &mut Vec<Inline>, allowlist: &Allowlist) {
+    let mut sanitized = Vec::new();
+    let mut open = Vec::new();
+
+    for mut inline in std::mem::take(inlines) {
+        match &mut inline {
+            Inline::Html(html) => *html = allowlist.clean(html, &mut open),
+            Inline::Link(url, _, inner) | Inline::Image(url, _, inner) if !allowlist.allows_url(url) => {
+                sanitize(inner, allowlist);
+                sanitized.append(inner);
+                continue;
+            },
+            Inline::Bold(inner)
+            | Inline::Italic(inner)
+            | Inline::Strikethrough(inner)
+            | Inline::Underline(inner)
+            | Inline::Link(_, _, inner)
+            | Inline::Image(_, _, inner)
+            | Inline::Extension(_, inner) => sanitize(inner, allowlist),
+            _ => {},
+        }
+
+        sanitized.push(inline);
+    }
+
+    if !open.is_empty() {
+        sanitized.push(Inline::Html(close(open)));
+    }
+
+    *inlines = sanitized;
+}
@@ -0,0 +1,246 @@
+use std::{collections::HashMap, sync::{Arc, LazyLock}};
+
+use chumsky::prelude::*;
+use regex::Regex;
+
+use crate::parser::Extra;
+
+use super::{ast::{self, Block, Inline}, diagnostic::SourceMap, nested};
+
+static DEFINITION: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r#"^ {0,3}\[([^\]^][^\]]*)\]:[ \t]*(<[^>]*>|\S+)(?:[ \t]+(?:"([^"]*)"|'([^']*)'|\(([^)]*)\)))?[ \t]*$"#).unwrap()
+});
+static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"(?:https?://|www\.)[^\s<]*[^\s<.,:;"')\]!?*_~]"#).unwrap());
+
+#[derive(Clone)]
+pub(super) struct Reference {
+    href: String,
+    title: Option<String>,
+}
+
+// where a link points, before reference labels are looked up
+enum Target<'src> {
+    Inline(Reference),
+    Label(&'src str),
+}
+
+// the text of a link and what it points to, as shared by links and images
+pub(super) fn link<'src>(inline: Boxed<'src, 'src, &'src str, Vec<Inline>, Extra<'src>>, references: Arc<HashMap<String, Reference>>) -> impl Parser<'src, &'src str, (Vec<Inline>, String, Option<String>), Extra<'src>> + Clone {
+    // link text can hold further brackets, like an image, as long as they're balanced
+    let label = recursive(|label| {
+        choice((
+            just('\\')
+                .then(any())
+                .ignored(),
+            label
+                .delimited_by(just('['), just(']'))
+                .ignored(),
+            none_of("[]")
+                .ignored(),
+        ))
+            .repeated()
+    })
+        .to_slice();
+    let padding = one_of(" \t\n").repeated();
+    let destination = choice((
+        none_of("<>\n")
+            .repeated()
+            .to_slice()
+            .delimited_by(just('<'), just('>')),
+        // parentheses are allowed as long as they're balanced, like in `/wiki/Rust_(programming_language)`
+        recursive(|destination| choice((
+            none_of(" \t\n()")
+                .ignored(),
+            destination
+                .delimited_by(just('('), just(')'))
+                .ignored(),
+        ))
+            .repeated())
+            .to_slice(),
+    ));
+    let title = choice((
+        none_of("\"")
+            .repeated()
+            .to_slice()
+            .delimited_by(just('"'), just('"')),
+        none_of("'")
+            .repeated()
+            .to_slice()
+            .delimited_by(just('\''), just('\'')),
+        none_of(")")
+            .repeated()
+            .to_slice()
+            .delimited_by(just('('), just(')')),
+    ));
+    let target = choice((
+        destination
+            .then(one_of(" \t\n")
+                .repeated()
+                .at_least(1)
+                .ignore_then(title)
+                .or_not())
+            .padded_by(padding)
+            .delimited_by(just('('), just(')'))
+            .map(|(href, title): (&str, Option<&str>)| Target::Inline(Reference {
+                href: href.to_owned(),
+                title: title.map(str::to_owned),
+            })),
+        label.clone()
+            .delimited_by(just('['), just(']'))
+            .map(Target::Label),
+    ));
+
+    label.clone()
+        .rewind()
+        .then(nested(inline.or_not(), label))
+        .delimited_by(just('['), just(']'))
+        .then(target.or_not())
+        .try_map(move |((raw, inner), target), span| {
+            // `[text][]` and `[text]` use the text itself as the label
+            let reference = match target {
+                Some(Target::Inline(reference)) => Some(reference),
+                Some(Target::Label(label)) if !label.trim().is_empty() => references.get(&normalize(label)).cloned(),
+                _ => references.get(&normalize(raw)).cloned(),
+            };
+
+            reference
+                .map(|reference| (inner.unwrap_or_default(), reference.href, reference.title))
+                .ok_or_else(|| Rich::custom(span, format!("Link reference [{}] is never defined", raw)))
+        })
+}
+
+// `<https://…>` and `<someone@example.com>`
+pub(super) fn autolink<'src>() -> impl Parser<'src, &'src str, Inline, Extra<'src>> + Clone {
+    let scheme = any()
+        .filter(char::is_ascii_alphabetic)
+        .then(any()
+            .filter(|c: &char| c.is_ascii_alphanumeric() || "+.-".contains(*c))
+            .repeated()
+            .at_least(1)
+            .at_most(31))
+        .then(just(':'));
+    let email = none_of(" \t\n<>@")
+        .repeated()
+        .at_least(1)
+        .then(just('@'))
+        .then(none_of(" \t\n<>@")
+            .repeated()
+            .at_least(1));
+
+    choice((
+        scheme
+            .then(none_of(" \t\n<>").repeated())
+            .to_slice()
+            .map(|url: &str| Inline::Link(url.to_owned(), None, vec![Inline::Text(url.to_owned())])),
+        email
+            .to_slice()
+            .map(|address: &str| Inline::Link(format!("mailto:{}", address), None, vec![Inline::Text(address.to_owned())])),
+    ))
+        .delimited_by(just('<'), just('>'))
+}
+
+// labels match case-insensitively and however their whitespace is spread out
+fn normalize(label: &str) -> String {
+    label
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .to_lowercase()
+}
+
+// removes `[label]: url "title"` definitions from the body, with the first one for a label winning
+pub(super) fn extract(body: &str) -> (String, HashMap<String, Reference>, SourceMap) {
+    let mut text = String::new();
+    let mut map = SourceMap::default();
+    let mut references = HashMap::new();
+    let mut fenced = false;
+
+    for (i, line) in body.lines().enumerate() {
+        if line.trim_start().starts_with("```") {
+            fenced = !fenced;
+        }
+
+        match DEFINITION.captures(line) {
+            Some(captures) if !fenced => {
+                let href = &captures[2];
+                let href = href
+                    .strip_prefix('<')
+                    .and_then(|href| href.strip_suffix('>'))
+                    .unwrap_or(href);
+                let title = captures.get(3)
+                    .or(captures.get(4))
+                    .or(captures.get(5))
+                    .map(|title| title.as_str().to_owned());
+
+                references
+                    .entry(normalize(&captures[1]))
+                    .or_insert(Reference {
+                        href: href.to_owned(),
+                        title,
+                    });
+            },
+            _ => {
+                if i > 0 {
+                    text.push('\n');
+                }
+
+                map.anchor(text.len(), line.as_ptr() as usize - body.as_ptr() as usize);
+                text.push_str(line);
+            },
+        }
+    }
+
+    (text, references, map)
+}
+
+// links bare `https://…` and `www.…` urls, leaving code, existing links and html attributes alone
+pub(super) fn link_urls(blocks: &mut [Block]) {
+    ast::visit_block_inlines(blocks, &mut link_text);
+}
+
+fn link_text(inlines: &mut Vec<Inline>) {
+    let mut linked = Vec::new();
+
+    for mut inline in std::mem::take(inlines) {
+        match &mut inline {
+            Inline::Text(text) => {
+                let mut last = 0;
+
+                for m in URL.find_iter(text) {
+                    if text[..m.start()].ends_with(['"', '\'', '=', '/']) {
+                        continue;
+                    }
+
+                    let href = if m.as_str().starts_with("www.") {
+                        format!("http://{}", m.as_str())
+                    } else {
+                        m.as_str().to_owned()
+                    };
+
+                    if last < m.start() {
+                        linked.push(Inline::Text(text[last..m.start()].to_owned()));
+                    }
+
+                    linked.push(Inline::Link(href, None, vec![Inline::Text(m.as_str().to_owned())]));
+                    last = m.end();
+                }
+
+                if last < text.len() {
+                    linked.push(Inline::Text(text[last..].to_owned()));
+                }
+
+                continue;
+            },
+            Inline::Bold(inner)
+            | Inline::Italic(inner)
+            | Inline::Strikethrough(inner)
+            | Inline::Underline(inner)
+            | Inline::Extension(_, inner) => link_text(inner),
+            _ => {},
+        }
+
+        linked.push(inline);
+    }
+
+    *inlines = linked;
+}
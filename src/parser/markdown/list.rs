@@ -0,0 +1,308 @@
+use chumsky::{prelude::*, text::{digits, newline}};
+
+use crate::parser::Extra;
+
+use super::{ast::{self, Block, ListItem}, diagnostic, document, Syntax};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Bullet(char),
+    Ordered(char),
+}
+
+struct Marker {
+    kind: Kind,
+    number: u64,
+    indent: usize,
+    offset: usize,
+}
+
+struct List<'a> {
+    kind: Kind,
+    start: u64,
+    items: Vec<Vec<&'a str>>,
+    loose: bool,
+}
+
+// a single run of items can hold several lists, one per change of marker
+pub(super) fn list<'src>(syntax: Syntax) -> impl Parser<'src, &'src str, Vec<Block>, Extra<'src>> + Clone {
+    let line = any()
+        .and_is(newline().not())
+        .repeated()
+        .at_least(1);
+    let marker = choice((
+        one_of("-*+").ignored(),
+        digits(10).at_least(1).at_most(9).then(one_of(".)")).ignored(),
+    ))
+        .then(just(' '))
+        .ignored();
+    let nested = choice((
+        one_of(" \t").ignored(),
+        marker,
+    ));
+    let interrupt = choice((
+        just("#").ignored(),
+        just(">").ignored(),
+        just("```").ignored(),
+        just("---").ignored(),
+        just("-#").ignored(),
+    ));
+
+    marker
+        .rewind()
+        .then(line)
+        .then(choice((
+            // items separated by blank lines, or indented content after them
+            newline()
+                .repeated()
+                .at_least(2)
+                .then(nested.rewind())
+                .then(line)
+                .ignored(),
+            // next item, indented content or lazy continuation
+            newline()
+                .then(nested.rewind().or(interrupt.not()))
+                .then(line)
+                .ignored(),
+        ))
+            .repeated())
+        .to_slice()
+        .validate(move |src: &str, e, emitter| {
+            let mut errors = Vec::new();
+            let blocks = build(src, &syntax, &mut errors);
+            diagnostic::emit(&errors, src, "list", e.span(), emitter);
+
+            blocks
+        })
+}
+
+// errors in the items are pushed to `errors`, at their offsets in `src`
+fn build<'a>(src: &str, syntax: &Syntax, errors: &mut Vec<Rich<'a, char>>) -> Vec<Block> {
+    let lines = src.lines().collect::<Vec<&str>>();
+
+    parse(&lines)
+        .iter()
+        .map(|list| list.build(src, syntax, errors))
+        .collect()
+}
+
+fn marker(line: &str) -> Option<Marker> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let rest = &line[indent..];
+    let digit_count = rest.len() - rest.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+
+    let (kind, number, width) = match rest.chars().next()? {
+        c @ ('-' | '*' | '+') => (Kind::Bullet(c), 0, 1),
+        _ if (1..=9).contains(&digit_count) => {
+            let delimiter = rest[digit_count..].chars().next()?;
+
+            if delimiter != '.' && delimiter != ')' {
+                return None;
+            }
+
+            (Kind::Ordered(delimiter), rest[..digit_count].parse().ok()?, digit_count + 1)
+        },
+        _ => return None,
+    };
+
+    let after = &rest[width..];
+    let spaces = after.len() - after.trim_start_matches(' ').len();
+
+    if spaces == 0 && !after.is_empty() {
+        return None;
+    }
+
+    // content indented by five or more spaces starts a code block, so only the first one belongs to the marker
+    let spaces = if spaces >= 5 || spaces == after.len() { 1 } else { spaces };
+
+    Some(Marker {
+        kind,
+        number,
+        indent,
+        offset: indent + width + spaces,
+    })
+}
+
+fn parse<'a>(lines: &[&'a str]) -> Vec<List<'a>> {
+    let mut lists: Vec<List<'a>> = Vec::new();
+    let mut offset = 0;
+
+    for line in lines {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+
+        if line.trim().is_empty() {
+            if let Some(item) = lists.last_mut().and_then(|list| list.items.last_mut()) {
+                item.push(&line[line.len()..]);
+            }
+
+            continue;
+        }
+
+        match (marker(line), lists.last_mut()) {
+            (Some(m), Some(list)) if m.indent < offset && m.kind == list.kind => {
+                if trim_blank_lines(list.items.last_mut()) {
+                    list.loose = true;
+                }
+
+                list.items.push(vec![&line[m.offset.min(line.len())..]]);
+                offset = m.offset;
+            },
+            (Some(m), last) if last.is_none() || m.indent < offset => {
+                trim_blank_lines(last.and_then(|list| list.items.last_mut()));
+
+                lists.push(List {
+                    kind: m.kind,
+                    start: m.number,
+                    items: vec![vec![&line[m.offset.min(line.len())..]]],
+                    loose: false,
+                });
+                offset = m.offset;
+            },
+            (_, Some(list)) => {
+                let content = if indent >= offset { &line[offset..] } else { line.trim_start() };
+
+                if let Some(item) = list.items.last_mut() {
+                    item.push(content);
+                }
+            },
+            (_, None) => {},
+        }
+    }
+
+    trim_blank_lines(lists.last_mut().and_then(|list| list.items.last_mut()));
+
+    lists
+}
+
+fn trim_blank_lines(item: Option<&mut Vec<&str>>) -> bool {
+    let mut trimmed = false;
+
+    if let Some(item) = item {
+        while item.len() > 1 && item.last().is_some_and(|l| l.trim().is_empty()) {
+            item.pop();
+            trimmed = true;
+        }
+    }
+
+    trimmed
+}
+
+// splits an item into its child blocks, each paired with whether a blank line came before it
+fn blocks<'a>(lines: &[&'a str]) -> Vec<(bool, bool, Vec<&'a str>)> {
+    let mut blocks = Vec::new();
+    let mut i = 0;
+
+    while i < lines.len() {
+        let start = i;
+
+        while i < lines.len() && lines[i].trim().is_empty() {
+            i += 1;
+        }
+
+        if i == lines.len() {
+            break;
+        }
+
+        let blank = i > start && !blocks.is_empty();
+        let is_list = marker(lines[i]).is_some();
+        let mut block = vec![lines[i]];
+        let mut fenced = lines[i].trim_start().starts_with("```");
+        i += 1;
+
+        while i < lines.len() {
+            let line = lines[i];
+
+            if is_list {
+                if line.trim().is_empty() {
+                    let next = lines[i..].iter().find(|l| !l.trim().is_empty());
+
+                    if !next.is_some_and(|l| l.starts_with([' ', '\t']) || marker(l).is_some()) {
+                        break;
+                    }
+                }
+            } else if fenced {
+                if line.trim_start().starts_with("```") {
+                    fenced = false;
+                }
+            } else if line.trim().is_empty() || marker(line).is_some() {
+                break;
+            } else if line.trim_start().starts_with("```") {
+                fenced = true;
+            }
+
+            block.push(line);
+            i += 1;
+        }
+
+        blocks.push((blank, is_list, block));
+    }
+
+    blocks
+}
+
+// a `[ ]` or `[x]` starting an item makes it a task, with the box taken off its first line
+fn task<'a>(item: &[&'a str]) -> (Option<bool>, Vec<&'a str>) {
+    let mut item = item.to_vec();
+    let checked = match item.first().and_then(|line| line.get(..4)) {
+        Some("[ ] ") => false,
+        Some("[x] " | "[X] ") => true,
+        _ => return (None, item),
+    };
+    item[0] = &item[0][4..];
+
+    (Some(checked), item)
+}
+
+impl<'a> List<'a> {
+    fn build<'e>(&self, src: &str, syntax: &Syntax, errors: &mut Vec<Rich<'e, char>>) -> Block {
+        let items = self.items
+            .iter()
+            .map(|item| {
+                let (task, item) = if syntax.tasks { task(item) } else { (None, item.clone()) };
+
+                (task, blocks(&item))
+            })
+            .collect::<Vec<_>>();
+        let loose = self.loose || items
+            .iter()
+            .any(|(_, blocks)| blocks.iter().any(|(blank, _, _)| *blank));
+
+        let items = items
+            .into_iter()
+            .map(|(task, blocks)| {
+                let mut item = Vec::new();
+
+                for (_, is_list, lines) in blocks {
+                    // the item's lines are slices of `src`, so their errors can be mapped back to it
+                    let (text, map) = diagnostic::join(&lines, src);
+                    let mut inner = Vec::new();
+
+                    if is_list {
+                        item.extend(build(&text, syntax, &mut inner));
+                    } else {
+                        match document(syntax).parse(&text).into_result() {
+                            Ok(blocks) => item.extend(blocks),
+                            Err(e) => inner.extend(e),
+                        }
+                    }
+
+                    errors.extend(inner.iter().map(|e| diagnostic::relocate(e, &text, "list item", |offset| map.map(offset))));
+                }
+
+                ListItem {
+                    task,
+                    blocks: item,
+                }
+            })
+            .collect();
+
+        Block::List(ast::List {
+            start: match self.kind {
+                Kind::Bullet(_) => None,
+                Kind::Ordered(_) => Some(self.start),
+            },
+            items,
+            loose,
+        })
+    }
+}
@@ -0,0 +1,647 @@
+use chumsky::{cache::{Cache, Cached}, input::Emitter, prelude::*, text::digits};
+
+use crate::parser::Extra;
+
+use super::{diagnostic, nested};
+
+// the tex that's supported, anything else is reported as an error:
+// - numbers, letters and the operators `+ - = < > ( ) [ ] , ; : ! / | * . ? '`
+// - `_` and `^` scripts, which big operators like `\sum` and `\lim` take as limits in display math
+// - `\frac`, `\dfrac`, `\tfrac`, `\binom`, `\sqrt` and `\sqrt[n]`
+// - `\text`, `\operatorname` and the font commands in `style`
+// - the accents in `accent`
+// - `\left` and `\right` around a group, each with a delimiter from `symbol` or `.` for none
+// - the environments in `ENVIRONMENTS`, with `&` between cells and `\\` between rows, which is unsupported outside them
+// - the greek letters, functions, operators, relations, arrows and spaces in `symbol`
+static ENVIRONMENTS: &[&str] = &["matrix", "pmatrix", "bmatrix", "Bmatrix", "vmatrix", "Vmatrix", "cases", "aligned", "align"];
+
+#[derive(Default)]
+struct Formula;
+
+impl Cached for Formula {
+    type Parser<'src> = Boxed<'src, 'src, &'src str, String, Extra<'src>>;
+
+    fn make_parser<'src>(self) -> Self::Parser<'src> {
+        formula().boxed()
+    }
+}
+
+thread_local! {
+    // built once, as every formula is parsed both when checking the markdown and when rendering it
+    static FORMULA: Cache<Formula> = Cache::default();
+}
+
+// reports anything unsupported in `tex`, found at `span` in the text being parsed
+pub(super) fn check<'src>(tex: &str, span: SimpleSpan, emitter: &mut Emitter<Rich<'src, char>>) {
+    FORMULA.with(|formula| diagnostic::emit(&formula.get().parse(tex).into_errors(), tex, "formula", span, emitter));
+}
+
+// converts a tex formula to mathml, showing the source as an error if it isn't supported
+pub(super) fn render(tex: &str, display: bool) -> String {
+    let inner = FORMULA
+        .with(|formula| formula.get().parse(tex).into_result().ok())
+        .unwrap_or_else(|| format!("<merror><mtext>{}</mtext></merror>", html_escape::encode_text(tex)));
+
+    if display {
+        format!("<math display=\"block\">{}</math>", inner)
+    } else {
+        format!("<math>{}</math>", inner)
+    }
+}
+
+fn formula<'src>() -> impl Parser<'src, &'src str, String, Extra<'src>> {
+    let row = recursive(|row: Recursive<dyn Parser<'src, &'src str, String, Extra<'src>> + 'src>| {
+        let group = row.clone()
+            .delimited_by(just('{'), just('}'))
+            .map(|inner| format!("<mrow>{}</mrow>", inner))
+            .boxed();
+        let letters = any()
+            .filter(char::is_ascii_alphabetic)
+            .repeated()
+            .at_least(1)
+            .to_slice();
+        let braced_text = none_of("}")
+            .repeated()
+            .to_slice()
+            .delimited_by(just('{'), just('}'))
+            .or(any().filter(|c: &char| c.is_alphanumeric()).to_slice());
+        let digit = any()
+            .filter(char::is_ascii_digit)
+            .map(|c| format!("<mn>{}</mn>", c));
+
+        let atom = recursive(|atom| {
+            // scripts and commands take a single token unless given a group
+            let argument = choice((
+                group.clone(),
+                digit,
+                atom,
+            ))
+                .padded()
+                .boxed();
+            let table = row.clone()
+                .separated_by(just('&'))
+                .collect::<Vec<String>>()
+                .separated_by(just("\\\\").padded())
+                .allow_trailing()
+                .collect::<Vec<Vec<String>>>();
+
+            choice((
+                // number
+                digits(10)
+                    .then(just('.').then(digits(10)).or_not())
+                    .to_slice()
+                    .map(|n| format!("<mn>{}</mn>", n)),
+                // letter
+                any()
+                    .filter(|c: &char| c.is_alphabetic())
+                    .map(|c| format!("<mi>{}</mi>", c)),
+                // operator
+                one_of("+-=<>()[],;:!/|*.?'")
+                    .map(|c| format!("<mo>{}</mo>", operator(c))),
+                command("frac").or(command("dfrac")).or(command("tfrac"))
+                    .ignore_then(argument.clone())
+                    .then(argument.clone())
+                    .map(|(numerator, denominator)| format!("<mfrac>{}{}</mfrac>", numerator, denominator)),
+                command("binom")
+                    .ignore_then(argument.clone())
+                    .then(argument.clone())
+                    .map(|(n, k)| format!("<mrow><mo>(</mo><mfrac linethickness=\"0\">{}{}</mfrac><mo>)</mo></mrow>", n, k)),
+                command("sqrt")
+                    .ignore_then(nested(row.clone(), none_of("]")
+                        .repeated()
+                        .to_slice())
+                        .delimited_by(just('['), just(']'))
+                        .or_not())
+                    .then(argument.clone())
+                    .map(|(index, radicand)| match index {
+                        Some(index) => format!("<mroot>{}<mrow>{}</mrow></mroot>", radicand, index),
+                        None => format!("<msqrt>{}</msqrt>", radicand),
+                    }),
+                command("text")
+                    .ignore_then(braced_text.padded())
+                    .map(|text| format!("<mtext>{}</mtext>", html_escape::encode_text(text))),
+                command("operatorname")
+                    .ignore_then(braced_text.padded())
+                    .map(|name| format!("<mi>{}</mi>", html_escape::encode_text(name))),
+                just('\\')
+                    .ignore_then(letters.filter(|name: &&str| style(name).is_some()))
+                    .then(braced_text.padded())
+                    .map(|(name, text)| styled(text, style(name).unwrap_or(""))),
+                just('\\')
+                    .ignore_then(letters.filter(|name: &&str| accent(name).is_some()))
+                    .then(argument.clone())
+                    .map(|(name, base)| match accent(name) {
+                        Some(("_", mark)) => format!("<munder accentunder=\"true\">{}<mo>{}</mo></munder>", base, mark),
+                        Some((_, mark)) => format!("<mover accent=\"true\">{}<mo>{}</mo></mover>", base, mark),
+                        None => base,
+                    }),
+                command("left")
+                    .ignore_then(delimiter().padded())
+                    .then(row.clone())
+                    .then_ignore(command("right"))
+                    .then(delimiter().padded())
+                    .map(|((open, inner), close)| format!("<mrow>{}{}{}</mrow>", open, inner, close)),
+                command("begin")
+                    .ignore_then(letters.then_ignore(just('*').or_not()).delimited_by(just('{'), just('}')))
+                    .then(table.padded())
+                    .then_ignore(command("end"))
+                    .then(letters.then_ignore(just('*').or_not()).delimited_by(just('{'), just('}')))
+                    .validate(|((name, rows), end), e, emitter| {
+                        if name != end {
+                            emitter.emit(Rich::custom(e.span(), format!("\\begin{{{}}} is closed by \\end{{{}}}", name, end)));
+                        } else if !ENVIRONMENTS.contains(&name) {
+                            emitter.emit(Rich::custom(e.span(), format!("Unsupported environment {}", name)));
+                        }
+
+                        environment(name, rows)
+                    }),
+                just('\\')
+                    .ignore_then(letters
+                        .filter(|name: &&str| !["right", "end"].contains(name))
+                        .or(one_of("{}|%#&$_,:;! ").to_slice()))
+                    .validate(|name, e, emitter| symbol(name).unwrap_or_else(|| {
+                        emitter.emit(Rich::custom(e.span(), format!("Unsupported command \\{}", name)));
+
+                        String::new()
+                    })),
+            ))
+                .padded()
+        })
+            .boxed();
+        let argument = choice((
+            group.clone(),
+            any().filter(char::is_ascii_digit).map(|c| format!("<mn>{}</mn>", c)),
+            atom.clone(),
+        ))
+            .padded()
+            .boxed();
+
+        // big operators put their scripts above and below in display math
+        let base = choice((
+            just('\\')
+                .ignore_then(letters.filter(|name: &&str| limits(name).is_some()))
+                .map(|name| (limits(name).unwrap_or_default(), true)),
+            group.map(|s| (s, false)),
+            atom.map(|s| (s, false)),
+        ));
+        let sub = just('_').ignore_then(argument.clone());
+        let sup = just('^').ignore_then(argument);
+
+        base
+            .then(choice((
+                sub.clone().then(sup.clone().or_not()).map(|(sub, sup)| (Some(sub), sup)),
+                sup.clone().then(sub.clone().or_not()).map(|(sup, sub)| (sub, Some(sup))),
+            ))
+                .or_not())
+            .padded()
+            .map(|((base, limits), scripts)| match (scripts, limits) {
+                (None, _) => base,
+                (Some((Some(sub), None)), false) => format!("<msub>{}{}</msub>", base, sub),
+                (Some((None, Some(sup))), false) => format!("<msup>{}{}</msup>", base, sup),
+                (Some((Some(sub), Some(sup))), false) => format!("<msubsup>{}{}{}</msubsup>", base, sub, sup),
+                (Some((Some(sub), None)), true) => format!("<munder>{}{}</munder>", base, sub),
+                (Some((None, Some(sup))), true) => format!("<mover>{}{}</mover>", base, sup),
+                (Some((Some(sub), Some(sup))), true) => format!("<munderover>{}{}{}</munderover>", base, sub, sup),
+                (Some((None, None)), _) => base,
+            })
+            .repeated()
+            .collect::<Vec<String>>()
+            .map(|items| items.concat())
+            .padded()
+    });
+
+    row.clone()
+        .separated_by(just("\\\\").validate(|_, e, emitter| {
+            emitter.emit(Rich::custom(e.span(), "Unsupported \\\\ outside an environment"));
+        }))
+        .collect::<Vec<String>>()
+        .map(|rows| rows.concat())
+}
+
+// matches `\name`, but not a longer command that starts with it
+fn command<'src>(name: &'static str) -> impl Parser<'src, &'src str, (), Extra<'src>> + Clone {
+    just('\\')
+        .then(just(name))
+        .then(any().filter(char::is_ascii_alphabetic).not())
+        .padded()
+        .ignored()
+}
+
+fn delimiter<'src>() -> impl Parser<'src, &'src str, String, Extra<'src>> + Clone {
+    choice((
+        just('.').to(String::new()),
+        one_of("()[]|/").map(|c| format!("<mo>{}</mo>", c)),
+        just('\\')
+            .ignore_then(any()
+                .filter(char::is_ascii_alphabetic)
+                .repeated()
+                .at_least(1)
+                .or(one_of("{}|").ignored())
+                .to_slice())
+            .validate(|name, e, emitter| symbol(name).unwrap_or_else(|| {
+                emitter.emit(Rich::custom(e.span(), format!("Unsupported delimiter \\{}", name)));
+
+                String::new()
+            })),
+    ))
+}
+
+fn environment(name: &str, rows: Vec<Vec<String>>) -> String {
+    let align = match name {
+        "cases" => " columnalign=\"left\"",
+        "aligned" | "align" => " columnalign=\"right left\"",
+        _ => "",
+    };
+    let table = format!("<mtable{}>{}</mtable>", align, rows
+        .iter()
+        .map(|cells| format!("<mtr>{}</mtr>", cells
+            .iter()
+            .map(|cell| format!("<mtd>{}</mtd>", cell))
+            .collect::<Vec<String>>()
+            .concat()))
+        .collect::<Vec<String>>()
+        .concat());
+    let (open, close) = match name {
+        "pmatrix" => ("(", ")"),
+        "bmatrix" => ("[", "]"),
+        "Bmatrix" => ("{", "}"),
+        "vmatrix" => ("|", "|"),
+        "Vmatrix" => ("‖", "‖"),
+        "cases" => ("{", ""),
+        _ => ("", ""),
+    };
+
+    if open.is_empty() && close.is_empty() {
+        table
+    } else {
+        let fence = |s: &str| if s.is_empty() { String::new() } else { format!("<mo>{}</mo>", s) };
+
+        format!("<mrow>{}{}{}</mrow>", fence(open), table, fence(close))
+    }
+}
+
+fn operator(c: char) -> String {
+    match c {
+        '-' => "−".to_owned(),
+        '*' => "∗".to_owned(),
+        '\'' => "′".to_owned(),
+        c => html_escape::encode_text(&c.to_string()).into_owned(),
+    }
+}
+
+fn limits(name: &str) -> Option<String> {
+    let op = match name {
+        "sum" => "∑",
+        "prod" => "∏",
+        "coprod" => "∐",
+        "bigcup" => "⋃",
+        "bigcap" => "⋂",
+        "bigoplus" => "⨁",
+        "bigotimes" => "⨂",
+        "lim" | "liminf" | "limsup" | "max" | "min" | "sup" | "inf" | "det" | "gcd" | "argmax" | "argmin" => {
+            let name = match name {
+                "liminf" => "lim inf",
+                "limsup" => "lim sup",
+                "argmax" => "arg max",
+                "argmin" => "arg min",
+                name => name,
+            };
+
+            return Some(format!("<mo movablelimits=\"true\">{}</mo>", name));
+        },
+        _ => return None,
+    };
+
+    Some(format!("<mo>{}</mo>", op))
+}
+
+fn style(name: &str) -> Option<&'static str> {
+    match name {
+        "mathrm" | "mathbf" | "mathit" | "mathbb" | "mathcal" | "mathscr" | "mathfrak" | "mathsf" | "mathtt" => Some(match name {
+            "mathrm" => "normal",
+            "mathbf" => "bold",
+            "mathit" => "italic",
+            "mathbb" => "double-struck",
+            "mathcal" | "mathscr" => "script",
+            "mathfrak" => "fraktur",
+            "mathsf" => "sans-serif",
+            _ => "monospace",
+        }),
+        _ => None,
+    }
+}
+
+// mathml core only knows `mathvariant="normal"`, so other styles use the unicode math alphabets instead
+fn styled(text: &str, style: &str) -> String {
+    let text = text
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .collect::<String>();
+
+    if style == "normal" {
+        return format!("<mi mathvariant=\"normal\">{}</mi>", html_escape::encode_text(&text));
+    }
+
+    let styled = text
+        .chars()
+        .map(|c| alphanumeric(c, style).unwrap_or(c))
+        .collect::<String>();
+
+    if text.chars().all(|c| c.is_ascii_digit()) {
+        format!("<mn>{}</mn>", styled)
+    } else {
+        format!("<mi>{}</mi>", html_escape::encode_text(&styled))
+    }
+}
+
+fn alphanumeric(c: char, style: &str) -> Option<char> {
+    // letters that were already in unicode before the math alphabets, and so are missing from them
+    let hole = match (style, c) {
+        ("italic", 'h') => Some('ℎ'),
+        ("double-struck", 'C') => Some('ℂ'),
+        ("double-struck", 'H') => Some('ℍ'),
+        ("double-struck", 'N') => Some('ℕ'),
+        ("double-struck", 'P') => Some('ℙ'),
+        ("double-struck", 'Q') => Some('ℚ'),
+        ("double-struck", 'R') => Some('ℝ'),
+        ("double-struck", 'Z') => Some('ℤ'),
+        ("script", 'B') => Some('ℬ'),
+        ("script", 'E') => Some('ℰ'),
+        ("script", 'F') => Some('ℱ'),
+        ("script", 'H') => Some('ℋ'),
+        ("script", 'I') => Some('ℐ'),
+        ("script", 'L') => Some('ℒ'),
+        ("script", 'M') => Some('ℳ'),
+        ("script", 'R') => Some('ℛ'),
+        ("script", 'e') => Some('ℯ'),
+        ("script", 'g') => Some('ℊ'),
+        ("script", 'o') => Some('ℴ'),
+        ("fraktur", 'C') => Some('ℭ'),
+        ("fraktur", 'H') => Some('ℌ'),
+        ("fraktur", 'I') => Some('ℑ'),
+        ("fraktur", 'R') => Some('ℜ'),
+        ("fraktur", 'Z') => Some('ℨ'),
+        _ => None,
+    };
+
+    if hole.is_some() {
+        return hole;
+    }
+
+    let (upper, lower, digit) = match style {
+        "bold" => (0x1D400, 0x1D41A, Some(0x1D7CE)),
+        "italic" => (0x1D434, 0x1D44E, None),
+        "double-struck" => (0x1D538, 0x1D552, Some(0x1D7D8)),
+        "script" => (0x1D49C, 0x1D4B6, None),
+        "fraktur" => (0x1D504, 0x1D51E, None),
+        "sans-serif" => (0x1D5A0, 0x1D5BA, Some(0x1D7E2)),
+        "monospace" => (0x1D670, 0x1D68A, Some(0x1D7F6)),
+        _ => return None,
+    };
+
+    match c {
+        'A'..='Z' => char::from_u32(upper + (c as u32 - 'A' as u32)),
+        'a'..='z' => char::from_u32(lower + (c as u32 - 'a' as u32)),
+        '0'..='9' => digit.and_then(|d| char::from_u32(d + (c as u32 - '0' as u32))),
+        _ => None,
+    }
+}
+
+fn accent(name: &str) -> Option<(&'static str, &'static str)> {
+    match name {
+        "hat" | "widehat" => Some(("^", "^")),
+        "bar" | "overline" => Some(("^", "‾")),
+        "vec" | "overrightarrow" => Some(("^", "→")),
+        "dot" => Some(("^", "˙")),
+        "ddot" => Some(("^", "¨")),
+        "tilde" | "widetilde" => Some(("^", "~")),
+        "underline" => Some(("_", "_")),
+        _ => None,
+    }
+}
+
+fn symbol(name: &str) -> Option<String> {
+    let identifier = |s: &str| Some(format!("<mi>{}</mi>", s));
+    let upright = |s: &str| Some(format!("<mi mathvariant=\"normal\">{}</mi>", s));
+    let operator = |s: &str| Some(format!("<mo>{}</mo>", s));
+    let space = |width: &str| Some(format!("<mspace width=\"{}\"/>", width));
+
+    match name {
+        "alpha" => identifier("α"),
+        "beta" => identifier("β"),
+        "gamma" => identifier("γ"),
+        "delta" => identifier("δ"),
+        "epsilon" => identifier("ϵ"),
+        "varepsilon" => identifier("ε"),
+        "zeta" => identifier("ζ"),
+        "eta" => identifier("η"),
+        "theta" => identifier("θ"),
+        "vartheta" => identifier("ϑ"),
+        "iota" => identifier("ι"),
+        "kappa" => identifier("κ"),
+        "lambda" => identifier("λ"),
+        "mu" => identifier("μ"),
+        "nu" => identifier("ν"),
+        "xi" => identifier("ξ"),
+        "pi" => identifier("π"),
+        "varpi" => identifier("ϖ"),
+        "rho" => identifier("ρ"),
+        "varrho" => identifier("ϱ"),
+        "sigma" => identifier("σ"),
+        "varsigma" => identifier("ς"),
+        "tau" => identifier("τ"),
+        "upsilon" => identifier("υ"),
+        "phi" => identifier("ϕ"),
+        "varphi" => identifier("φ"),
+        "chi" => identifier("χ"),
+        "psi" => identifier("ψ"),
+        "omega" => identifier("ω"),
+        "Gamma" => upright("Γ"),
+        "Delta" => upright("Δ"),
+        "Theta" => upright("Θ"),
+        "Lambda" => upright("Λ"),
+        "Xi" => upright("Ξ"),
+        "Pi" => upright("Π"),
+        "Sigma" => upright("Σ"),
+        "Upsilon" => upright("Υ"),
+        "Phi" => upright("Φ"),
+        "Psi" => upright("Ψ"),
+        "Omega" => upright("Ω"),
+        "infty" => identifier("∞"),
+        "partial" => identifier("∂"),
+        "nabla" => identifier("∇"),
+        "emptyset" | "varnothing" => identifier("∅"),
+        "hbar" => identifier("ℏ"),
+        "ell" => identifier("ℓ"),
+        "Re" => identifier("ℜ"),
+        "Im" => identifier("ℑ"),
+        "aleph" => identifier("ℵ"),
+        "sin" | "cos" | "tan" | "cot" | "sec" | "csc" | "arcsin" | "arccos" | "arctan" | "sinh" | "cosh" | "tanh" | "coth"
+        | "log" | "ln" | "lg" | "exp" | "deg" | "dim" | "ker" | "arg" | "hom" | "Pr" => identifier(name),
+        "int" => operator("∫"),
+        "iint" => operator("∬"),
+        "iiint" => operator("∭"),
+        "oint" => operator("∮"),
+        "times" => operator("×"),
+        "cdot" => operator("⋅"),
+        "pm" => operator("±"),
+        "mp" => operator("∓"),
+        "div" => operator("÷"),
+        "ast" => operator("∗"),
+        "star" => operator("⋆"),
+        "circ" => operator("∘"),
+        "bullet" => operator("∙"),
+        "oplus" => operator("⊕"),
+        "otimes" => operator("⊗"),
+        "leq" | "le" => operator("≤"),
+        "geq" | "ge" => operator("≥"),
+        "neq" | "ne" => operator("≠"),
+        "ll" => operator("≪"),
+        "gg" => operator("≫"),
+        "approx" => operator("≈"),
+        "equiv" => operator("≡"),
+        "sim" => operator("∼"),
+        "simeq" => operator("≃"),
+        "cong" => operator("≅"),
+        "propto" => operator("∝"),
+        "perp" => operator("⊥"),
+        "parallel" => operator("∥"),
+        "mid" => operator("∣"),
+        "to" | "rightarrow" => operator("→"),
+        "leftarrow" | "gets" => operator("←"),
+        "leftrightarrow" => operator("↔"),
+        "Rightarrow" => operator("⇒"),
+        "Leftarrow" => operator("⇐"),
+        "Leftrightarrow" => operator("⇔"),
+        "mapsto" => operator("↦"),
+        "implies" => operator("⟹"),
+        "iff" => operator("⟺"),
+        "in" => operator("∈"),
+        "notin" => operator("∉"),
+        "ni" => operator("∋"),
+        "subset" => operator("⊂"),
+        "subseteq" => operator("⊆"),
+        "supset" => operator("⊃"),
+        "supseteq" => operator("⊇"),
+        "cup" => operator("∪"),
+        "cap" => operator("∩"),
+        "setminus" => operator("∖"),
+        "land" | "wedge" => operator("∧"),
+        "lor" | "vee" => operator("∨"),
+        "neg" | "lnot" => operator("¬"),
+        "forall" => operator("∀"),
+        "exists" => operator("∃"),
+        "angle" => operator("∠"),
+        "prime" => operator("′"),
+        "ldots" | "dots" => operator("…"),
+        "cdots" => operator("⋯"),
+        "vdots" => operator("⋮"),
+        "ddots" => operator("⋱"),
+        "langle" => operator("⟨"),
+        "rangle" => operator("⟩"),
+        "lfloor" => operator("⌊"),
+        "rfloor" => operator("⌋"),
+        "lceil" => operator("⌈"),
+        "rceil" => operator("⌉"),
+        "lvert" | "rvert" | "vert" => operator("|"),
+        "lVert" | "rVert" | "Vert" | "|" => operator("‖"),
+        "{" | "lbrace" => operator("{"),
+        "}" | "rbrace" => operator("}"),
+        "%" | "#" | "$" | "_" => operator(name),
+        "&" => operator("&amp;"),
+        "," => space("0.1667em"),
+        ":" => space("0.2222em"),
+        ";" => space("0.2778em"),
+        " " => space("0.2222em"),
+        "!" => space("-0.1667em"),
+        "quad" => space("1em"),
+        "qquad" => space("2em"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chumsky::Parser;
+
+    use super::{formula, render};
+
+    #[test]
+    fn scripts() {
+        let res = render("\\sum_{i=1}^n x_i^2", false);
+        let expected = format!("<math><munderover><mo>∑</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></munderover><msubsup><mi>x</mi><mi>i</mi><mn>2</mn></msubsup></math>");
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn commands() {
+        let res = render("\\sqrt[3]{\\alpha} \\cdot \\mathbb{R} \\left( \\hat{v} \\right)", false);
+        let expected = format!("<math><mroot><mrow><mi>α</mi></mrow><mrow><mn>3</mn></mrow></mroot><mo>⋅</mo><mi>ℝ</mi><mrow><mo>(</mo><mover accent=\"true\"><mrow><mi>v</mi></mrow><mo>^</mo></mover><mo>)</mo></mrow></math>");
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn matrix() {
+        let res = render("\\begin{bmatrix} 1 & 0 \\\\ 0 & 1 \\end{bmatrix}", true);
+        let expected = format!("<math display=\"block\"><mrow><mo>[</mo><mtable><mtr><mtd><mn>1</mn></mtd><mtd><mn>0</mn></mtd></mtr><mtr><mtd><mn>0</mn></mtd><mtd><mn>1</mn></mtd></mtr></mtable><mo>]</mo></mrow></math>");
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn fraction_scripts() {
+        let res = render("e^{\\frac{x}{2}} + a_\\frac12", false);
+        let expected = format!("<math><msup><mi>e</mi><mrow><mfrac><mrow><mi>x</mi></mrow><mrow><mn>2</mn></mrow></mfrac></mrow></msup><mo>+</mo><msub><mi>a</mi><mfrac><mn>1</mn><mn>2</mn></mfrac></msub></math>");
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn left_right() {
+        let res = render("\\left\\{ x \\right. \\left\\langle \\frac{1}{2} \\right|", false);
+        let expected = format!("<math><mrow><mo>{{</mo><mi>x</mi></mrow><mrow><mo>⟨</mo><mfrac><mrow><mn>1</mn></mrow><mrow><mn>2</mn></mrow></mfrac><mo>|</mo></mrow></math>");
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn environments() {
+        let res = render("f(x) = \\begin{cases} 1 & x > 0 \\\\ 0 & \\text{otherwise} \\end{cases}", true);
+        let expected = format!("<math display=\"block\"><mi>f</mi><mo>(</mo><mi>x</mi><mo>)</mo><mo>=</mo><mrow><mo>{{</mo><mtable columnalign=\"left\"><mtr><mtd><mn>1</mn></mtd><mtd><mi>x</mi><mo>&gt;</mo><mn>0</mn></mtd></mtr><mtr><mtd><mn>0</mn></mtd><mtd><mtext>otherwise</mtext></mtd></mtr></mtable></mrow></math>");
+
+        assert_eq!(expected, res);
+
+        let res = render("\\begin{align*} a &= b \\end{align*}", true);
+        let expected = format!("<math display=\"block\"><mtable columnalign=\"right left\"><mtr><mtd><mi>a</mi></mtd><mtd><mo>=</mo><mi>b</mi></mtd></mtr></mtable></math>");
+
+        assert_eq!(expected, res);
+    }
+
+    #[test]
+    fn unsupported() {
+        let errors = |tex: &str| formula()
+            .parse(tex)
+            .into_errors()
+            .iter()
+            .map(|e| (e.span().into_range(), e.to_string()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![(0..5, format!("Unsupported command \\meow"))], errors("\\meow + 1"));
+        assert_eq!(vec![(0..31, format!("Unsupported environment tabular"))], errors("\\begin{tabular} a \\end{tabular}"));
+        assert_eq!(vec![(0..28, format!("\\begin{{matrix}} is closed by \\end{{cases}}"))], errors("\\begin{matrix} a \\end{cases}"));
+        assert_eq!(vec![(5..10, format!("Unsupported delimiter \\meow"))], errors("\\left\\meow x \\right)"));
+        assert_eq!(vec![(2..4, format!("Unsupported \\\\ outside an environment"))], errors("a \\\\ b"));
+        // rendered as an error rather than partly converted
+        assert_eq!(format!("<math><merror><mtext>\\meow + 1</mtext></merror></math>"), render("\\meow + 1", false));
+    }
+
+    #[test]
+    fn invalid() {
+        let res = render("{x", false);
+        let expected = format!("<math><merror><mtext>{{x</mtext></merror></math>");
+
+        assert_eq!(expected, res);
+    }
+}
@@ -1,21 +1,66 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
-use anyhow::{anyhow, bail, Result};
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
-use chumsky::{prelude::*, text::{ident, newline}};
+use chumsky::{prelude::*, text::{self, newline}};
 use extension::{MarkdownExtension, MarkdownExtensionList};
-use fronma::parser::parse;
-use tera::Value;
+use footnote::Footnotes;
+use heading::Headings;
+use highlight::Highlighting;
+use html::RawHtml;
+use schema::Schema;
+use tera::{Tera, Value};
 
 use crate::data::{Item, State};
+use ast::{Block, Inline};
+use diagnostic::SyntaxErrors;
+use shortcode::Shortcode;
 
-use super::{line_terminator, ParserProcedure};
+use super::{fail, line_terminator, Extra, ParserProcedure};
 
+pub mod ast;
 pub mod extension;
+mod callout;
+mod definition;
+mod diagnostic;
+mod footnote;
+mod frontmatter;
+mod heading;
+mod highlight;
+pub mod html;
+mod link;
+mod list;
+mod math;
+pub mod schema;
+mod shortcode;
+mod summary;
+mod table;
+mod typography;
+
+// changes the blocks of a parsed body before they're rendered
+type Transform = Arc<dyn Fn(&mut Vec<Block>)>;
 
 #[derive(Clone)]
 pub struct MarkdownParser {
     extensions: Vec<MarkdownExtension>,
+    transforms: Vec<Transform>,
+    heading_anchors: bool,
+    link_urls: bool,
+    raw_html: RawHtml,
+    typography: Option<String>,
+    schema: Option<Schema>,
+    highlighting: Option<Highlighting>,
+    summary: Option<usize>,
+}
+
+// what the grammar is built from, besides the source itself
+#[derive(Clone, Default)]
+struct Syntax {
+    extensions: Vec<MarkdownExtension>,
+    references: Arc<HashMap<String, link::Reference>>,
+    // whether `extension::tasks` and `extension::definitions` are among the extensions
+    tasks: bool,
+    definitions: bool,
 }
 
 impl MarkdownParser {
@@ -25,8 +70,218 @@ impl MarkdownParser {
         extensions.push(extension);
 
         Self {
-            extensions
+            extensions,
+            ..self.clone()
+        }
+    }
+
+    // runs over the parsed body and each footnote definition, in the order they were added
+    pub fn transform<F: Fn(&mut Vec<Block>) + 'static>(&self, transform: F) -> Self {
+        let mut transforms = self.transforms.clone();
+
+        transforms.push(Arc::new(transform));
+
+        Self {
+            transforms,
+            ..self.clone()
+        }
+    }
+
+    pub fn heading_anchors(self) -> Self {
+        Self {
+            heading_anchors: true,
+            ..self
+        }
+    }
+
+    // turns bare `https://…` and `www.…` urls into links
+    pub fn link_urls(self) -> Self {
+        Self {
+            link_urls: true,
+            ..self
+        }
+    }
+
+    // curly quotes, dashes and ellipses, with quotes for `language` unless a page's frontmatter sets its own `lang`
+    pub fn smart_punctuation<S: Into<String>>(self, language: S) -> Self {
+        Self {
+            typography: Some(language.into()),
+            ..self
+        }
+    }
+
+    // checks each page's frontmatter before it's merged into its properties
+    pub fn schema<S: Into<Schema>>(self, schema: S) -> Self {
+        Self {
+            schema: Some(schema.into()),
+            ..self
+        }
+    }
+
+    // html in the source is passed through by default
+    pub fn raw_html(self, policy: RawHtml) -> Self {
+        Self {
+            raw_html: policy,
+            ..self
+        }
+    }
+
+    // sets `summary`, `plain_text`, `word_count` and `reading_time`, with the summary falling back to the first `words` words
+    pub fn summarize(self, words: usize) -> Self {
+        Self {
+            summary: Some(words),
+            ..self
+        }
+    }
+
+    pub fn highlight<S: Into<String>>(self, theme: S) -> Self {
+        Self {
+            highlighting: Some(Highlighting::Inline(theme.into())),
+            ..self
+        }
+    }
+
+    pub fn highlight_classes(self) -> Self {
+        Self {
+            highlighting: Some(Highlighting::Classed),
+            ..self
+        }
+    }
+
+    // shortcodes are left as placeholders and footnote definitions are dropped, as both are only filled in when rendering
+    pub fn parse(&self, body: &str) -> Result<Vec<Block>> {
+        let (body, _, shortcode_map) = shortcode::extract(body)?;
+        let (body, _, footnote_map) = footnote::extract(&body);
+        let (body, references, link_map) = link::extract(&body);
+        let mut blocks = self.blocks(&body, &Arc::new(references), |offset| shortcode_map.map(footnote_map.map(link_map.map(offset))))?;
+        Headings::new(self.heading_anchors).anchor(&mut blocks);
+
+        Ok(blocks)
+    }
+
+    fn blocks<F: Fn(usize) -> usize>(&self, body: &str, references: &Arc<HashMap<String, link::Reference>>, map: F) -> Result<Vec<Block>> {
+        let syntax = Syntax {
+            extensions: self.extensions.clone(),
+            references: references.clone(),
+            tasks: self.extensions.contains(&extension::tasks()),
+            definitions: self.extensions.contains(&extension::definitions()),
+        };
+        let trimmed = body.len() - body.trim_start().len();
+        let body = body.trim();
+        let mut blocks = if body.is_empty() {
+            Vec::new()
+        } else {
+            document(&syntax)
+                .parse(body)
+                .into_result()
+                .map_err(|errors| SyntaxErrors::new(errors, |offset| map(offset + trimmed)))?
+        };
+
+        if self.link_urls {
+            link::link_urls(&mut blocks);
+        }
+
+        if let Some(language) = &self.typography {
+            typography::smarten(&mut blocks, language);
+        }
+
+        // before the transforms, so any html they add is trusted
+        html::apply(&mut blocks, &self.raw_html);
+
+        for transform in &self.transforms {
+            transform(&mut blocks);
+        }
+
+        Ok(blocks)
+    }
+
+    // the html of `body` with its shortcodes filled in and the text inside them rendered the same way, sharing the
+    // document's heading ids and footnote numbers, with the definitions added to `footnotes` for its section
+    // also returns the blocks and the rendered shortcodes
+    fn html(&self, tera: &Tera, body: &str, map: &dyn Fn(usize) -> usize, footnotes: &mut Footnotes, headings: &mut Headings) -> Result<(String, Vec<Block>, Vec<String>)> {
+        let (body, shortcodes, shortcode_map) = shortcode::extract(body)?;
+        let (body, definitions, footnote_map) = footnote::extract(&body);
+        let (body, references, link_map) = link::extract(&body);
+        let references = Arc::new(references);
+        let mut blocks = self.blocks(&body, &references, |offset| map(shortcode_map.map(footnote_map.map(link_map.map(offset)))))?;
+
+        for (name, definition) in definitions {
+            let blocks = self.blocks(&definition.text, &references, |offset| map(shortcode_map.map(definition.map.map(offset))))?;
+            footnotes.define(name, blocks);
+        }
+
+        let inline = shortcode::inline(&mut blocks);
+        let mut rendered = Vec::new();
+
+        // each block's headings and footnotes are numbered after the ones in the shortcodes before it
+        for i in 0..blocks.len() {
+            headings.anchor(&mut blocks[i..=i]);
+            footnotes.number(&mut blocks[i..=i]);
+
+            let until = shortcode::last(&ast::html(&blocks[i..=i])).map_or(0, |last| last + 1);
+
+            while rendered.len() < until {
+                let inline = inline.contains(&rendered.len());
+                rendered.push(self.shortcode(tera, &shortcodes[rendered.len()], inline, map, footnotes, headings)?);
+            }
+        }
+
+        // the rest are in footnotes
+        while rendered.len() < shortcodes.len() {
+            rendered.push(self.shortcode(tera, &shortcodes[rendered.len()], false, map, footnotes, headings)?);
+        }
+
+        Ok((shortcode::resolve(ast::html(&blocks), &rendered), blocks, rendered))
+    }
+
+    // the text inside a shortcode rendered within a line has to be a single paragraph, which loses its `<p>`
+    fn shortcode(&self, tera: &Tera, shortcode: &Shortcode, inline: bool, map: &dyn Fn(usize) -> usize, footnotes: &mut Footnotes, headings: &mut Headings) -> Result<String> {
+        let inner = match &shortcode.inner {
+            Some(inner) if inline => {
+                let (_, blocks, rendered) = self.html(tera, inner, &|offset| map(shortcode.offset + offset), footnotes, headings)?;
+
+                match blocks.as_slice() {
+                    [] | [Block::Paragraph(_)] => Some(shortcode::resolve(ast::render(&blocks, true), &rendered)),
+                    _ => return Err(anyhow!("Shortcode '{}' inside a line of text can only hold a single paragraph", shortcode.name)),
+                }
+            },
+            Some(inner) => Some(self.html(tera, inner, &|offset| map(shortcode.offset + offset), footnotes, headings)?.0),
+            None => None,
+        };
+
+        shortcode.render(tera, inner)
+    }
+
+    // over the html rather than the blocks, so code in shortcode templates is highlighted too
+    fn highlight_html(&self, html: String) -> Result<String> {
+        match &self.highlighting {
+            Some(highlighting) => highlight::highlight(&html, highlighting),
+            None => Ok(html),
+        }
+    }
+
+    fn render(&self, tera: &Tera, body: &str) -> Result<(String, HashMap<String, Value>)> {
+        let mut footnotes = Footnotes::default();
+        let mut headings = Headings::new(self.heading_anchors);
+        let (res, blocks, rendered) = self.html(tera, body, &|offset| offset, &mut footnotes, &mut headings)?;
+        let res = self.highlight_html(res + &footnotes.section()?)?;
+
+        let mut properties = HashMap::new();
+        properties.insert("toc".to_owned(), headings.toc());
+
+        if let Some(words) = self.summary {
+            let text = shortcode::strip(&ast::text(&blocks));
+            let word_count = text.split_whitespace().count();
+            let summary = shortcode::resolve(ast::html(&summary::excerpt(&blocks, words)), &rendered);
+            let summary = self.highlight_html(footnote::strip(&summary))?;
+
+            properties.insert("summary".to_owned(), Value::from(summary));
+            properties.insert("plain_text".to_owned(), Value::from(text));
+            properties.insert("word_count".to_owned(), Value::from(word_count));
+            properties.insert("reading_time".to_owned(), Value::from(word_count.div_ceil(summary::READING_SPEED)));
         }
+
+        Ok((res, properties))
     }
 }
 
@@ -35,26 +290,40 @@ impl ParserProcedure for MarkdownParser {
     fn default() -> Self {
         Self {
             extensions: Vec::new(),
+            transforms: Vec::new(),
+            heading_anchors: false,
+            link_urls: false,
+            raw_html: RawHtml::Verbatim,
+            typography: None,
+            schema: None,
+            highlighting: None,
+            summary: None,
         }
     }
 
     async fn process(&self, state: &mut State, item: &Item) -> Result<Item> {
         let text = String::from_utf8(item.bytes.clone())?;
-        let (headers, body) = match parse::<HashMap<String, Value>>(&text) {
-            Ok(val) => (val.headers, val.body),
-            Err(e) => match e {
-                fronma::error::Error::MissingBeginningLine => (HashMap::new(), text.as_str()),
-                fronma::error::Error::MissingEndingLine => bail!("Frontmatter is missing closing triple dash"),
-                fronma::error::Error::SerdeYaml(e) => bail!("Failed to parse YAML frontmatter: {}", e),
-            },
-        };
+        let (mut headers, body) = frontmatter::split(&text)?;
+
+        if let Some(schema) = &self.schema {
+            schema.validate(&item.path.display().to_string(), &mut headers)?;
+        }
 
-        let body = body.to_owned().trim().to_owned();
-        let res = make_parser(&self.extensions).parse(&body).into_result().map_err(|_e| anyhow!("Failed to parse markdown"))?;
-        // markdown is stupid and I'm lazy, so have a band-aid fix
-        let res = format!("<p>{}</p>", res).replace("<p></p>", "");
+        // the body is a suffix of the file, so this is where it starts after the frontmatter
+        let offset = text.len() - body.trim_start().len();
+        // a page written in another language gets its own quotes
+        let parser = match (&self.typography, headers.get("lang").and_then(Value::as_str)) {
+            (Some(_), Some(language)) => self.clone().smart_punctuation(language),
+            _ => self.clone(),
+        };
+        let (res, rendered) = parser.render(&state.tera, body.trim())
+            .map_err(|e| match e.downcast::<SyntaxErrors>() {
+                Ok(errors) => anyhow!(errors.report(&item.path.display().to_string(), &text, offset)),
+                Err(e) => e,
+            })?;
 
         let mut properties = item.properties.clone();
+        properties.extend(rendered);
         properties.extend(headers);
 
         Ok(Item {
@@ -65,141 +334,192 @@ impl ParserProcedure for MarkdownParser {
     }
 }
 
-fn make_parser<'src>(extensions: &Vec<MarkdownExtension>) -> impl Parser<'src, &'src str, String> + Clone {
-    recursive(|this| {
-        let block = block(this.clone(), extensions.clone()).boxed();
-        choice((
-            block.clone(),
-            inline(this, block, extensions.clone()),
-        ))
+// renders a fragment, so a lone paragraph (a table cell, a tight list item) stays inline
+#[cfg(test)]
+fn make_parser<'src>(extensions: &[MarkdownExtension]) -> impl Parser<'src, &'src str, String, Extra<'src>> + Clone {
+    let syntax = Syntax {
+        extensions: extensions.to_vec(),
+        tasks: extensions.contains(&extension::tasks()),
+        definitions: extensions.contains(&extension::definitions()),
+        ..Default::default()
+    };
+
+    document(&syntax).map(|blocks| ast::render(&blocks, blocks.len() == 1))
+}
+
+fn document<'src>(syntax: &Syntax) -> impl Parser<'src, &'src str, Vec<Block>, Extra<'src>> + Clone {
+    let blank_line = one_of(" \t")
+        .repeated()
+        .then(newline());
+
+    blank_line
+        .repeated()
+        .ignore_then(block(syntax.clone())
+            .then_ignore(line_terminator())
+            .then_ignore(blank_line.repeated())
             .repeated()
-            .at_least(1)
-            .collect::<Vec<String>>()
-            .map(|elements| elements.concat())
-    })
+            .collect::<Vec<Vec<Block>>>())
+        .then_ignore(text::whitespace())
+        .map(|blocks| blocks.concat())
 }
 
-fn block<'src>(parser: Recursive<dyn Parser<'src, &'src str, String> + 'src>, extensions: Vec<MarkdownExtension>) -> impl Parser<'src, &'src str, String> + Clone {
-    recursive(|this| {
-        let inline = inline(parser.clone(), this.clone().boxed(), extensions.clone());
-        let block = newline()
+// lists come back as a run of blocks, so every block does
+fn block<'src>(syntax: Syntax) -> impl Parser<'src, &'src str, Vec<Block>, Extra<'src>> + Clone {
+    let inline = inline(syntax.clone()).boxed();
+    let line = any()
+        .and_is(newline().not())
+        .repeated()
+        .at_least(1);
+    let blank = one_of(" \t")
+        .repeated()
+        .then(line_terminator());
+    let line_end = one_of(" \t")
+        .repeated()
+        .then(line_terminator().rewind());
+
+    let interrupt = choice((
+        // headers
+        just('#')
             .repeated()
-            .ignore_then(
-                choice((
-                    // headers
-                    inline.clone()
-                        .nested_in(just("### ")
-                            .ignore_then(any()
-                                .and_is(line_terminator().not())
-                                .repeated()
-                                .at_least(1)
-                                .to_slice()))
-                        .map(|s| format!("</p><h3>{}</h3><p>", s)),
-                    inline.clone()
-                        .nested_in(just("## ")
-                            .ignore_then(any()
-                                .and_is(line_terminator().not())
-                                .repeated()
-                                .at_least(1)
-                                .to_slice()))
-                        .map(|s| format!("</p><h2>{}</h2><p>", s)),
-                    inline.clone()
-                        .nested_in(just("# ")
-                            .ignore_then(any()
-                                .and_is(line_terminator().not())
-                                .repeated()
-                                .at_least(1)
-                                .to_slice()))
-                        .map(|s| format!("</p><h1>{}</h1><p>", s)),
-                    // thematic break
-                    just("---\n")
-                        .to(format!("<hr/>")),
-                    extensions.build_block_parser(inline.clone().boxed()),
-                )))
-            .boxed();
+            .at_least(1)
+            .at_most(6)
+            .count()
+            .then_ignore(just(' '))
+            .then(nested(inline.clone(), line
+                .to_slice()
+                .map(str::trim)))
+            .map(|(level, inner)| vec![Block::Heading(level, None, inner)]),
+        // thematic break
+        just("---")
+            .then(line_end.clone())
+            .to(vec![Block::Rule]),
+        // code block
+        just("```")
+            .ignore_then(any()
+                .and_is(newline().not())
+                .and_is(just("```").not())
+                .repeated()
+                .collect::<String>()
+                .then_ignore(newline())
+                .or_not()
+                .map(|info| info.filter(|info| !info.is_empty())))
+            .then(any()
+                .and_is(just("```").not())
+                .repeated()
+                .at_least(1)
+                .collect::<String>())
+            .then_ignore(just("```"))
+            .then_ignore(line_end.clone())
+            .map(|(info, code)| vec![Block::Code(info, code)]),
+        // display math
+        just("$$")
+            .ignore_then(any()
+                .and_is(just("$$").not())
+                .repeated()
+                .at_least(1)
+                .to_slice()
+                .validate(|tex: &str, e, emitter| {
+                    math::check(tex, e.span(), emitter);
 
-        choice((
-            block,
-            // paragraph break
-            newline().repeated().exactly(3).to(format!("</p><p>")),
-            // line break
-            newline().repeated().exactly(2).to(format!("<br/>")),
-        ))
-    })
+                    tex
+                }))
+            .then_ignore(just("$$"))
+            .then_ignore(line_end.clone())
+            .map(|tex: &str| vec![Block::Math(tex.trim().to_owned())]),
+        // html comment
+        just("<!--")
+            .then(any()
+                .and_is(just("-->").not())
+                .repeated())
+            .then(just("-->"))
+            .to_slice()
+            .then_ignore(line_end)
+            .map(|comment: &str| vec![Block::Html(comment.to_owned())]),
+        // html block
+        html::block()
+            .map(|html| vec![html]),
+        // callouts, before extensions so `quote` doesn't take alerts
+        callout::callout(syntax.clone())
+            .map(|callout| vec![callout]),
+        // extensions go before lists, so ones can start the same way as list items
+        syntax.extensions.clone().build_block_parser(inline.clone())
+            .map(|block| vec![block]),
+        // definition lists
+        if syntax.definitions {
+            definition::definitions(inline.clone()).boxed()
+        } else {
+            fail().to(Block::Rule).boxed()
+        }
+            .map(|block| vec![block]),
+        // lists
+        list::list(syntax.clone()),
+        // tables
+        table::table(syntax.clone())
+            .map(|table| vec![table]),
+    )).boxed();
+
+    choice((
+        interrupt.clone(),
+        // paragraph, which runs until a blank line or another block
+        nested(inline, line
+            .and_is(blank.clone().not())
+            .then(newline()
+                .then(blank.not())
+                .then(interrupt.not())
+                .then(line)
+                .repeated())
+            .to_slice()
+            .map(str::trim))
+            .map(|inner| vec![Block::Paragraph(inner)]),
+    ))
+}
+
+// `parser.nested_in(outer)`, but with the errors `parser` emits kept where they are in the input,
+// as chumsky leaves them relative to the text `outer` matched
+fn nested<'src, O, P, Q>(parser: P, outer: Q) -> impl Parser<'src, &'src str, O, Extra<'src>> + Clone
+where
+    P: Parser<'src, &'src str, O, Extra<'src>> + Clone,
+    Q: Parser<'src, &'src str, &'src str, Extra<'src>> + Clone,
+{
+    outer
+        .map_with(|text: &'src str, e| (text, e.span().start + (text.as_ptr() as usize - e.slice().as_ptr() as usize)))
+        .try_map(move |(text, start), span| {
+            let (output, errors) = parser.parse(text).into_output_errors();
+            let mut errors = errors
+                .iter()
+                .map(|e| diagnostic::relocate(e, text, "formatted text", |offset| start + offset))
+                .collect::<Vec<Rich<char>>>();
+
+            match output {
+                Some(output) => Ok((output, errors)),
+                None => Err(errors.pop().unwrap_or_else(|| Rich::custom(span, "Failed to parse"))),
+            }
+        })
+        .validate(|(output, errors), _, emitter| {
+            for e in errors {
+                emitter.emit(e);
+            }
+
+            output
+        })
 }
 
-fn inline<'src>(parser: Recursive<dyn Parser<'src, &'src str, String> + 'src>, block: Boxed<'src, 'src, &'src str, String>, extensions: Vec<MarkdownExtension>) -> impl Parser<'src, &'src str, String> + Clone {
+fn inline<'src>(syntax: Syntax) -> impl Parser<'src, &'src str, Vec<Inline>, Extra<'src>> + Clone {
     recursive(|this| {
         let inline = choice((
+            // footnote reference
+            footnote::reference(),
+            // autolink
+            link::autolink(),
+            // html tag
+            html::inline(),
             // image
             just('!')
-                .ignore_then(
-                    group((
-                        this.clone()
-                            .nested_in(any()
-                                .and_is(just(']').not())
-                                .repeated()
-                                .to_slice())
-                            .or_not()
-                            .delimited_by(just('['), just(']')),
-                        this.clone()
-                            .nested_in(any()
-                                .and_is(just(')').not())
-                                .repeated()
-                                .to_slice())
-                            .or_not()
-                            .delimited_by(just('('), just(')')),
-                    )))
-                .map(|(text, link)| {
-                    format!("<img src=\"{}\" alt=\"{}\"/>", link.unwrap_or_else(String::new), text.unwrap_or_else(String::new))
-                }),
+                .ignore_then(link::link(this.clone().boxed(), syntax.references.clone()))
+                .map(|(alt, src, title)| Inline::Image(src, title, alt)),
             // link
-            group((
-                this.clone()
-                    .nested_in(any()
-                        .and_is(just(']').not())
-                        .repeated()
-                        .to_slice())
-                    .or_not()
-                    .delimited_by(just('['), just(']')),
-                this.clone()
-                    .nested_in(any()
-                        .and_is(just(')').not())
-                        .repeated()
-                        .to_slice())
-                    .or_not()
-                    .delimited_by(just('('), just(')')),
-            ))
-                .map(|(text, link)| {
-                    format!("<a href=\"{}\">{}</a>", link.unwrap_or_else(String::new), text.unwrap_or_else(String::new))
-                }),
-            // code block
-            just("```")
-                .ignore_then(any()
-                    .and_is(newline().not())
-                    .and_is(just("```").not())
-                    .repeated()
-                    .at_least(1)
-                    .collect::<String>()
-                    .then_ignore(newline())
-                    .or_not())
-                .then(any()
-                    .and_is(just("```").not())
-                    .repeated()
-                    .at_least(1)
-                    .to_slice())
-                .then_ignore(just("```"))
-                .map(|(special, inner)| {
-                    let inner = html_escape::encode_safe(inner);
-                    match special {
-                        Some(special) => match special.rsplit_once(".") {
-                            Some((_, language)) => format!("<small>{}</small><code class=\"language-{}\">{}</code>", special, language, inner),
-                            None => format!("<code class=\"language-{}\">{}</code>", special, inner),
-                        },
-                        None => format!("<code>{}</code>", inner),
-                    }
-                })
-                .map(|s| format!("</p><pre>{}</pre><p>", s)),
+            link::link(this.clone().boxed(), syntax.references.clone())
+                .map(|(text, href, title)| Inline::Link(href, title, text)),
             // code line
             any()
                 .and_is(just('`').not())
@@ -207,93 +527,163 @@ fn inline<'src>(parser: Recursive<dyn Parser<'src, &'src str, String> + 'src>, b
                 .at_least(1)
                 .to_slice()
                 .padded_by(just('`'))
-                .map(|inner| format!("<code>{}</code>", html_escape::encode_safe(inner))),
+                .map(|inner: &str| Inline::Code(inner.to_owned())),
+            // display math
+            just("$$")
+                .ignore_then(any()
+                    .and_is(just("$$").not())
+                    .repeated()
+                    .at_least(1)
+                    .to_slice()
+                    .validate(|tex: &str, e, emitter| {
+                        math::check(tex, e.span(), emitter);
+
+                        tex
+                    }))
+                .then_ignore(just("$$"))
+                .map(|tex: &str| Inline::Math(true, tex.trim().to_owned())),
+            // inline math, which like pandoc can't start or end on a space or be followed by a digit, so prices stay text
+            choice((
+                just('\\')
+                    .then(any())
+                    .ignored(),
+                none_of("$")
+                    .ignored(),
+            ))
+                .repeated()
+                .at_least(1)
+                .to_slice()
+                .filter(|tex: &&str| !tex.starts_with(char::is_whitespace) && !tex.ends_with(char::is_whitespace))
+                .validate(|tex: &str, e, emitter| {
+                    math::check(tex, e.span(), emitter);
+
+                    tex
+                })
+                .delimited_by(just('$'), just('$'))
+                .then_ignore(any().filter(char::is_ascii_digit).not())
+                .map(|tex: &str| Inline::Math(false, tex.to_owned())),
             // bold
-            this.clone()
-                .nested_in(just("**")
-                    .ignore_then(any()
-                        .and_is(just("**").not())
-                        .repeated()
-                        .at_least(1)
-                        .then(just('*')
-                            .and_is(just("***"))
-                            .repeated())
-                        .to_slice())
-                    .then_ignore(just("**")))
-                .map(|inner| format!("<b>{}</b>", inner)),
+            nested(this.clone(), just("**")
+                .ignore_then(any()
+                    .and_is(just("**").not())
+                    .repeated()
+                    .at_least(1)
+                    .then(just('*')
+                        .and_is(just("***"))
+                        .repeated())
+                    .to_slice())
+                .then_ignore(just("**")))
+                .map(Inline::Bold),
             // italic
-            this.clone()
-                .nested_in(just('*')
-                    .ignore_then(any()
-                        .and_is(just('*').not())
-                        .repeated()
-                        .at_least(1)
-                        .then(just('*')
-                            .and_is(just("**"))
-                            .repeated())
-                        .to_slice())
-                    .then_ignore(just('*')))
-                .map(|inner| format!("<i>{}</i>", inner)),
+            nested(this.clone(), just('*')
+                .ignore_then(any()
+                    .and_is(just('*').not())
+                    .repeated()
+                    .at_least(1)
+                    .then(just('*')
+                        .and_is(just("**"))
+                        .repeated())
+                    .to_slice())
+                .then_ignore(just('*')))
+                .map(Inline::Italic),
             // strikethrough
-            this.clone()
-                .nested_in(just("~~")
-                    .ignore_then(any()
-                        .and_is(just("~~").not())
-                        .repeated()
-                        .at_least(1)
-                        .then(just('~')
-                            .and_is(just("~~~"))
-                            .repeated())
-                        .to_slice())
-                    .then_ignore(just("~~")))
-                .map(|inner| format!("<s>{}</s>", inner)),
+            nested(this.clone(), just("~~")
+                .ignore_then(any()
+                    .and_is(just("~~").not())
+                    .repeated()
+                    .at_least(1)
+                    .then(just('~')
+                        .and_is(just("~~~"))
+                        .repeated())
+                    .to_slice())
+                .then_ignore(just("~~")))
+                .map(Inline::Strikethrough),
             // underline
-            this.clone()
-                .nested_in(just("__")
-                    .ignore_then(any()
-                        .and_is(just("__").not())
-                        .repeated()
-                        .at_least(1)
-                        .then(just('_')
-                            .and_is(just("___"))
-                            .repeated())
-                        .to_slice())
-                    .then_ignore(just("__")))
-                .map(|inner| format!("<u>{}</u>", inner)),
-            extensions.build_inline_parser(this.boxed()),
+            nested(this.clone(), just("__")
+                .ignore_then(any()
+                    .and_is(just("__").not())
+                    .repeated()
+                    .at_least(1)
+                    .then(just('_')
+                        .and_is(just("___"))
+                        .repeated())
+                    .to_slice())
+                .then_ignore(just("__")))
+                .map(Inline::Underline),
+            syntax.extensions.clone().build_inline_parser(this.boxed()),
         )).boxed();
+        let line_break = choice((
+            // hard break, from two trailing spaces or a backslash
+            choice((
+                just(' ')
+                    .repeated()
+                    .at_least(2)
+                    .ignored(),
+                just('\\')
+                    .ignored(),
+            ))
+                .then(newline())
+                .to(Inline::LineBreak),
+            // soft break
+            just(' ')
+                .repeated()
+                .then(newline())
+                .to(Inline::SoftBreak),
+        ))
+            .then_ignore(one_of(" \t").repeated());
 
         choice((
+            line_break.clone(),
             // escape char
             just("\\")
                 .ignore_then(any()
-                    .map(|c| format!("{}", c))),
-            // manual wrapping
-            newline()
-                .repeated()
-                .exactly(1)
-                .to(format!("")),
+                    .map(|c: char| Inline::Text(c.to_string()))),
             inline.clone(),
             any()
                 .and_is(inline.not())
-                .and_is(newline().not())
+                .and_is(line_break.not())
+                .and_is(just('\\').then(any()).not())
                 .repeated()
                 .at_least(1)
-                .collect::<String>(),
-        )).and_is(block.not()).repeated().at_least(1).collect::<Vec<String>>().map(|elements| elements.concat())
+                .collect::<String>()
+                .map(Inline::Text),
+        )).repeated().at_least(1).collect::<Vec<Inline>>().map(merge_text)
     })
 }
 
+// joins the runs of text that escaped characters split apart
+fn merge_text(inlines: Vec<Inline>) -> Vec<Inline> {
+    let mut merged: Vec<Inline> = Vec::new();
+
+    for inline in inlines {
+        match (merged.last_mut(), inline) {
+            (Some(Inline::Text(last)), Inline::Text(text)) => last.push_str(&text),
+            (_, inline) => merged.push(inline),
+        }
+    }
+
+    merged
+}
+
 #[cfg(test)]
 mod tests {
     mod block {
         use chumsky::Parser;
+        use serde_json::json;
+        use tera::Tera;
 
-        use crate::parser::markdown::make_parser;
+        use crate::parser::{markdown::{diagnostic::SyntaxErrors, extension, make_parser, MarkdownParser}, ParserProcedure};
+
+        fn report(body: &str) -> String {
+            let p = MarkdownParser::default();
+            let e = p.render(&Tera::default(), body).unwrap_err();
+
+            e.downcast::<SyntaxErrors>().unwrap().report("meow.md", body, 0)
+        }
 
         #[test]
         fn header1() {
-            let p = make_parser(&vec![]);
+            let p = make_parser(&[]);
             let res = p.parse("# meow").into_result().unwrap();
             let expected = format!("<h1>meow</h1>");
 
@@ -302,7 +692,7 @@ mod tests {
 
         #[test]
         fn header2() {
-            let p = make_parser(&vec![]);
+            let p = make_parser(&[]);
             let res = p.parse("## meow").into_result().unwrap();
             let expected = format!("<h2>meow</h2>");
 
@@ -311,7 +701,7 @@ mod tests {
 
         #[test]
         fn header3() {
-            let p = make_parser(&vec![]);
+            let p = make_parser(&[]);
             let res = p.parse("### meow").into_result().unwrap();
             let expected = format!("<h3>meow</h3>");
 
@@ -319,97 +709,889 @@ mod tests {
         }
 
         #[test]
-        fn paragraph() {
-            let p = make_parser(&vec![]);
-            let res = p.parse("meow\n\n\nmeow").into_result().unwrap();
-            let expected = format!("meow</p><p>meow");
+        fn header6() {
+            let p = make_parser(&[]);
+            let res = p.parse("###### meow").into_result().unwrap();
+            let expected = format!("<h6>meow</h6>");
 
             assert_eq!(expected, res);
         }
-    }
 
-    mod inline {
-        use chumsky::Parser;
+        #[test]
+        fn header_ids() {
+            let p = MarkdownParser::default();
+            let (res, _) = p.render(&Tera::default(), "# Meow, *mrrp*!\n\n## meow mrrp\n\n## meow mrrp").unwrap();
+            let expected = format!("<h1 id=\"meow-mrrp\">Meow, <i>mrrp</i>!</h1><h2 id=\"meow-mrrp-1\">meow mrrp</h2><h2 id=\"meow-mrrp-2\">meow mrrp</h2>");
 
-        use crate::parser::markdown::make_parser;
+            assert_eq!(expected, res);
+        }
 
         #[test]
-        fn image_embed() {
-            let p = make_parser(&vec![]);
-            let res = p.parse("![this is an image](https://it.is.from.here)").into_result().unwrap();
-            let expected = format!("<img src=\"https://it.is.from.here\" alt=\"this is an image\"/>");
+        fn paragraph_errors() {
+            let res = report("meow\nmrrp **nya $\\meow$**");
+            let expected = format!("Failed to parse markdown in meow.md\nmeow.md:2:13: Unsupported command \\meow\n  |\n2 | mrrp **nya $\\meow$**\n  |             ^^^^^");
 
             assert_eq!(expected, res);
         }
 
         #[test]
-        fn link_embed() {
-            let p = make_parser(&vec![]);
-            let res = p.parse("[this is a link](https://it.goes.here)").into_result().unwrap();
-            let expected = format!("<a href=\"https://it.goes.here\">this is a link</a>");
+        fn line_break_errors() {
+            let res = report("meow $a \\\\ b$");
+            let expected = format!("Failed to parse markdown in meow.md\nmeow.md:1:9: Unsupported \\\\ outside an environment\n  |\n1 | meow $a \\\\ b$\n  |         ^^");
 
             assert_eq!(expected, res);
         }
 
         #[test]
-        fn code_block() {
-            let p = make_parser(&vec![]);
-            let res = p.parse("```meow```").into_result().unwrap();
-            let expected = format!("<pre><code>meow</code></pre>");
+        fn list_errors() {
+            let res = report("- meow\n- mrrp\n\n  - nya $\\meow$");
+            let expected = format!("Failed to parse markdown in meow.md\nmeow.md:4:10: Unsupported command \\meow\n  |\n4 |   - nya $\\meow$\n  |          ^^^^^");
 
             assert_eq!(expected, res);
         }
 
         #[test]
-        fn code_line() {
-            let p = make_parser(&vec![]);
-            let res = p.parse("`meow`").into_result().unwrap();
-            let expected = format!("<code>meow</code>");
+        fn table_errors() {
+            let res = report("| a | b |\n|---|---|\n| meow | mrrp \\| $\\meow$ |");
+            let expected = format!("Failed to parse markdown in meow.md\nmeow.md:3:19: Unsupported command \\meow\n  |\n3 | | meow | mrrp \\| $\\meow$ |\n  |                   ^^^^^");
 
             assert_eq!(expected, res);
         }
 
         #[test]
-        fn bold() {
-            let p = make_parser(&vec![]);
-            let res = p.parse("**meow**").into_result().unwrap();
-            let expected = format!("<b>meow</b>");
+        fn callout_errors() {
+            let res = report(":::note mrrp\n> [!TIP]\n> meow $\\meow$\n:::");
+            let expected = format!("Failed to parse markdown in meow.md\nmeow.md:3:9: Unsupported command \\meow\n  |\n3 | > meow $\\meow$\n  |         ^^^^^");
 
             assert_eq!(expected, res);
         }
 
         #[test]
-        fn italic() {
-            let p = make_parser(&vec![]);
-            let res = p.parse("*meow*").into_result().unwrap();
-            let expected = format!("<i>meow</i>");
+        fn shortcode_errors() {
+            let mut tera = Tera::default();
+            tera.add_raw_template("shortcodes/note.html", "{{ inner }}").unwrap();
+            let body = "meow\n\n{{< note >}}\n[^a]: mrrp $\\meow$\n{{< /note >}}";
+            let e = MarkdownParser::default().render(&tera, body).unwrap_err();
+            let res = e.downcast::<SyntaxErrors>().unwrap().report("meow.md", body, 0);
+            let expected = format!("Failed to parse markdown in meow.md\nmeow.md:4:13: Unsupported command \\meow\n  |\n4 | [^a]: mrrp $\\meow$\n  |             ^^^^^");
 
             assert_eq!(expected, res);
         }
 
         #[test]
-        fn bold_and_italic() {
-            let p = make_parser(&vec![]);
-            let res = p.parse("***meow***").into_result().unwrap();
-            let expected = format!("<b><i>meow</i></b>");
+        fn footnote_errors() {
+            let res = report("meow[^1]\n\n[^1]: mrrp\n\n    nya $\\meow$");
+            let expected = format!("Failed to parse markdown in meow.md\nmeow.md:5:10: Unsupported command \\meow\n  |\n5 |     nya $\\meow$\n  |          ^^^^^");
 
             assert_eq!(expected, res);
         }
 
         #[test]
-        fn strikethrough() {
-            let p = make_parser(&vec![]);
-            let res = p.parse("~~meow~~").into_result().unwrap();
-            let expected = format!("<s>meow</s>");
+        fn header_anchors() {
+            let p = MarkdownParser::default().heading_anchors();
+            let (res, _) = p.render(&Tera::default(), "## meow").unwrap();
+            let expected = format!("<h2 id=\"meow\">meow <a class=\"anchor\" href=\"#meow\" aria-hidden=\"true\">#</a></h2>");
 
             assert_eq!(expected, res);
         }
 
         #[test]
-        fn underline() {
-            let p = make_parser(&vec![]);
-            let res = p.parse("__meow__").into_result().unwrap();
-            let expected = format!("<u>meow</u>");
+        fn table_of_contents() {
+            let p = MarkdownParser::default();
+            let (_, properties) = p.render(&Tera::default(), "# meow\n\n## mrrp\n\n#### nya\n\n## purr\n\n# hiss").unwrap();
+            let expected = json!([
+                { "level": 1, "id": "meow", "title": "meow", "children": [
+                    { "level": 2, "id": "mrrp", "title": "mrrp", "children": [
+                        { "level": 4, "id": "nya", "title": "nya", "children": [] },
+                    ] },
+                    { "level": 2, "id": "purr", "title": "purr", "children": [] },
+                ] },
+                { "level": 1, "id": "hiss", "title": "hiss", "children": [] },
+            ]);
+
+            assert_eq!(expected, properties["toc"]);
+        }
+
+        #[test]
+        fn document_headings_only() {
+            let p = MarkdownParser::default();
+            let (res, properties) = p.render(&Tera::default(), "# meow\n\n<div><h2>mrrp</h2></div>\n\nnya[^1]\n\n[^1]: purr\n\n    ### hiss").unwrap();
+            let expected = json!([{ "level": 1, "id": "meow", "title": "meow", "children": [] }]);
+
+            assert!(res.starts_with("<h1 id=\"meow\">meow</h1><div><h2>mrrp</h2></div>"));
+            assert!(res.contains("<h3>hiss</h3>"));
+            assert_eq!(expected, properties["toc"]);
+        }
+
+        #[test]
+        fn shortcode() {
+            let mut tera = Tera::default();
+            tera.autoescape_on(Vec::new());
+            tera.add_raw_template("shortcodes/figure.html", "<figure><img src=\"{{ src }}\"/><figcaption>{{ caption }}</figcaption></figure>").unwrap();
+            let p = MarkdownParser::default();
+            let (res, _) = p.render(&tera, "meow\n\n\n{{< figure src=\"/cat.png\" caption=\"a cat\" >}}").unwrap();
+            let expected = format!("<p>meow</p><figure><img src=\"/cat.png\"/><figcaption>a cat</figcaption></figure>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn paired_shortcode() {
+            let mut tera = Tera::default();
+            tera.autoescape_on(Vec::new());
+            tera.add_raw_template("shortcodes/note.html", "<aside class=\"{{ kind }}\">{{ inner }}</aside>").unwrap();
+            tera.add_raw_template("shortcodes/mark.html", "<mark>{{ inner }}</mark>").unwrap();
+            let p = MarkdownParser::default();
+            let (res, _) = p.render(&tera, "{{< note kind=tip >}}\n**meow** {{< mark >}}*mrrp*{{< /mark >}}\n\n{{< note kind=nested >}}\nnya\n{{< /note >}}\n{{< /note >}}").unwrap();
+            let expected = format!("<aside class=\"tip\"><p><b>meow</b> <mark><i>mrrp</i></mark></p><aside class=\"nested\"><p>nya</p></aside></aside>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn inline_shortcode_blocks() {
+            let mut tera = Tera::default();
+            tera.add_raw_template("shortcodes/mark.html", "<mark>{{ inner }}</mark>").unwrap();
+            let p = MarkdownParser::default();
+            let res = p.render(&tera, "meow {{< mark >}}\n- mrrp\n- nya\n{{< /mark >}}").unwrap_err().to_string();
+            let expected = format!("Shortcode 'mark' inside a line of text can only hold a single paragraph");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn shortcode_in_code() {
+            let p = MarkdownParser::default();
+            let (res, _) = p.render(&Tera::default(), "meow `{{< figure >}}` and `mrrp {{< figure >}}`").unwrap();
+            let expected = format!("<p>meow <code>{{{{&lt; figure &gt;}}}}</code> and <code>mrrp {{{{&lt; figure &gt;}}}}</code></p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn shortcode_shares_document() {
+            let mut tera = Tera::default();
+            tera.autoescape_on(Vec::new());
+            tera.add_raw_template("shortcodes/note.html", "<aside>{{ inner }}</aside>").unwrap();
+            let p = MarkdownParser::default().heading_anchors();
+            let (res, _) = p.render(&tera, "## meow\n\nmrrp[^a]\n\n{{< note >}}\n## meow\n\nnya[^b] and mrrp[^a]\n\n[^b]: inside\n{{< /note >}}\n\n[^a]: outside").unwrap();
+            let expected = format!("<h2 id=\"meow\">meow <a class=\"anchor\" href=\"#meow\" aria-hidden=\"true\">#</a></h2><p>mrrp<sup class=\"footnote-ref\" id=\"fnref-1\"><a href=\"#fn-1\">1</a></sup></p><aside><h2 id=\"meow-1\">meow <a class=\"anchor\" href=\"#meow-1\" aria-hidden=\"true\">#</a></h2><p>nya<sup class=\"footnote-ref\" id=\"fnref-2\"><a href=\"#fn-2\">2</a></sup> and mrrp<sup class=\"footnote-ref\" id=\"fnref-1-2\"><a href=\"#fn-1\">1</a></sup></p></aside><section class=\"footnotes\"><ol><li id=\"fn-1\"><p>outside <a href=\"#fnref-1\" class=\"footnote-backref\">↩</a> <a href=\"#fnref-1-2\" class=\"footnote-backref\">↩</a></p></li><li id=\"fn-2\"><p>inside <a href=\"#fnref-2\" class=\"footnote-backref\">↩</a></p></li></ol></section>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn alert() {
+            let p = make_parser(&[extension::quote()]);
+            let res = p.parse("> [!WARNING]\n> **meow**\n>\n> - mrrp\n\n> nya").into_result().unwrap();
+            let expected = format!("<aside class=\"callout callout-warning\"><p class=\"callout-title\">Warning</p><p><b>meow</b></p><ul><li>mrrp</li></ul></aside><blockquote>nya</blockquote>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn container() {
+            let p = make_parser(&[]);
+            let res = p.parse("meow\n:::tip A *purring* cat\nmrrp\n\n:::note\nnya\n:::\n:::\n\nhiss").into_result().unwrap();
+            let expected = format!("<p>meow</p><aside class=\"callout callout-tip\"><p class=\"callout-title\">A <i>purring</i> cat</p><p>mrrp</p><aside class=\"callout callout-note\"><p class=\"callout-title\">Note</p><p>nya</p></aside></aside><p>hiss</p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn unclosed_container() {
+            let p = make_parser(&[]);
+            let res = p.parse(":::tip\nmeow").into_result().unwrap();
+            let expected = format!(":::tip\nmeow");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn escaped_shortcode() {
+            let p = MarkdownParser::default();
+            let (res, _) = p.render(&Tera::default(), "{{</* figure */>}}").unwrap();
+            let expected = format!("<p>{{{{&lt; figure &gt;}}}}</p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn paragraph() {
+            let p = make_parser(&[]);
+            let res = p.parse("meow\n\nmeow").into_result().unwrap();
+            let expected = format!("<p>meow</p><p>meow</p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn paragraph_blank_lines() {
+            let p = make_parser(&[]);
+            let res = p.parse("\nmeow\nmrrp\n \n\n\nnya\n").into_result().unwrap();
+            let expected = format!("<p>meow\nmrrp</p><p>nya</p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn paragraph_around_blocks() {
+            let p = MarkdownParser::default();
+            let (res, _) = p.render(&Tera::default(), "meow\n# mrrp\nnya\n```\npurr\n\nhiss\n```\n---\nmeow").unwrap();
+            let expected = format!("<p>meow</p><h1 id=\"mrrp\">mrrp</h1><p>nya</p><pre><code>purr\n\nhiss\n</code></pre><hr/><p>meow</p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn hard_break() {
+            let p = make_parser(&[]);
+            let res = p.parse("meow  \nmrrp\\\n  nya").into_result().unwrap();
+            let expected = format!("meow<br/>mrrp<br/>nya");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn unordered_list() {
+            let p = make_parser(&[]);
+            let res = p.parse("- meow\n- mrrp").into_result().unwrap();
+            let expected = format!("<ul><li>meow</li><li>mrrp</li></ul>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn ordered_list() {
+            let p = make_parser(&[]);
+            let res = p.parse("1. meow\n2. mrrp").into_result().unwrap();
+            let expected = format!("<ol><li>meow</li><li>mrrp</li></ol>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn ordered_list_start() {
+            let p = make_parser(&[]);
+            let res = p.parse("3) meow\n4) mrrp").into_result().unwrap();
+            let expected = format!("<ol start=\"3\"><li>meow</li><li>mrrp</li></ol>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn nested_list() {
+            let p = make_parser(&[]);
+            let res = p.parse("- meow\n  1. mrrp\n  2. nya\n- **purr**").into_result().unwrap();
+            let expected = format!("<ul><li>meow<ol><li>mrrp</li><li>nya</li></ol></li><li><b>purr</b></li></ul>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn loose_list() {
+            let p = make_parser(&[]);
+            let res = p.parse("- meow\n\n- mrrp\n\n  nya").into_result().unwrap();
+            let expected = format!("<ul><li><p>meow</p></li><li><p>mrrp</p><p>nya</p></li></ul>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn task_list() {
+            let p = make_parser(&[extension::tasks()]);
+            let res = p.parse("* [x] **meow**\n* [ ] mrrp\n* [nya](/purr)").into_result().unwrap();
+            let expected = format!("<ul><li class=\"task\"><input type=\"checkbox\" disabled checked/> <b>meow</b></li><li class=\"task\"><input type=\"checkbox\" disabled/> mrrp</li><li><a href=\"/purr\">nya</a></li></ul>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn nested_task_list() {
+            let p = make_parser(&[extension::tasks()]);
+            let res = p.parse("1. [X] meow\n   + [ ] mrrp\n   + nya\n\n2. [ ] purr").into_result().unwrap();
+            let expected = format!("<ol><li class=\"task\"><p><input type=\"checkbox\" disabled checked/> meow</p><ul><li class=\"task\"><input type=\"checkbox\" disabled/> mrrp</li><li>nya</li></ul></li><li class=\"task\"><p><input type=\"checkbox\" disabled/> purr</p></li></ol>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn task_list_disabled() {
+            let p = make_parser(&[]);
+            let res = p.parse("- [x] meow\n- [ ] mrrp").into_result().unwrap();
+            let expected = format!("<ul><li>[x] meow</li><li>[ ] mrrp</li></ul>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn adjacent_lists() {
+            let p = make_parser(&[]);
+            let res = p.parse("1. meow\n\n- mrrp").into_result().unwrap();
+            let expected = format!("<ol><li>meow</li></ol><ul><li>mrrp</li></ul>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn list_after_text() {
+            let p = make_parser(&[]);
+            let res = p.parse("meow\n- mrrp").into_result().unwrap();
+            let expected = format!("<p>meow</p><ul><li>mrrp</li></ul>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn table() {
+            let p = make_parser(&[]);
+            let res = p.parse("| meow | mrrp |\n| --- | --- |\n| a | b |\n| c |").into_result().unwrap();
+            let expected = format!("<table><thead><tr><th>meow</th><th>mrrp</th></tr></thead><tbody><tr><td>a</td><td>b</td></tr><tr><td>c</td><td></td></tr></tbody></table>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn table_alignment() {
+            let p = make_parser(&[]);
+            let res = p.parse("a | b | c\n:-- | :-: | --:").into_result().unwrap();
+            let expected = format!("<table><thead><tr><th style=\"text-align: left\">a</th><th style=\"text-align: center\">b</th><th style=\"text-align: right\">c</th></tr></thead></table>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn table_inline() {
+            let p = make_parser(&[]);
+            let res = p.parse("| meow |\n|-|\n| **a \\| b** |").into_result().unwrap();
+            let expected = format!("<table><thead><tr><th>meow</th></tr></thead><tbody><tr><td><b>a | b</b></td></tr></tbody></table>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn footnotes() {
+            let p = MarkdownParser::default();
+            let (res, _) = p.render(&Tera::default(), "meow[^cat] mrrp[^1] nya[^cat]\n\n[^1]: **purr**\n[^cat]: a cat").unwrap();
+            let expected = format!("<p>meow<sup class=\"footnote-ref\" id=\"fnref-1\"><a href=\"#fn-1\">1</a></sup> mrrp<sup class=\"footnote-ref\" id=\"fnref-2\"><a href=\"#fn-2\">2</a></sup> nya<sup class=\"footnote-ref\" id=\"fnref-1-2\"><a href=\"#fn-1\">1</a></sup></p><section class=\"footnotes\"><ol><li id=\"fn-1\"><p>a cat <a href=\"#fnref-1\" class=\"footnote-backref\">↩</a> <a href=\"#fnref-1-2\" class=\"footnote-backref\">↩</a></p></li><li id=\"fn-2\"><p><b>purr</b> <a href=\"#fnref-2\" class=\"footnote-backref\">↩</a></p></li></ol></section>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn footnote_markup_in_html() {
+            let p = MarkdownParser::default();
+            let (res, _) = p.render(&Tera::default(), "meow <sup class=\"footnote-ref\" data-footnote=\"zz\"></sup>").unwrap();
+            let expected = format!("<p>meow <sup class=\"footnote-ref\" data-footnote=\"zz\"></sup></p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn undefined_footnote() {
+            let p = MarkdownParser::default();
+            let res = p.render(&Tera::default(), "meow[^missing]").unwrap_err();
+
+            assert_eq!("Footnote [^missing] is referenced but never defined", res.to_string());
+        }
+    }
+
+    mod inline {
+        use std::collections::HashMap;
+
+        use chumsky::Parser;
+        use tera::Tera;
+
+        use crate::{data::{Item, State}, parser::{markdown::{make_parser, MarkdownParser}, ParserProcedure}};
+
+        #[test]
+        fn image_embed() {
+            let p = make_parser(&[]);
+            let res = p.parse("![this is an image](https://it.is.from.here)").into_result().unwrap();
+            let expected = format!("<img src=\"https://it.is.from.here\" alt=\"this is an image\"/>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn link_embed() {
+            let p = make_parser(&[]);
+            let res = p.parse("[this is a link](https://it.goes.here)").into_result().unwrap();
+            let expected = format!("<a href=\"https://it.goes.here\">this is a link</a>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn link_title() {
+            let p = make_parser(&[]);
+            let res = p.parse("[meow](/mrrp \"a & b\") ![nya](/purr.png 'hiss')").into_result().unwrap();
+            let expected = format!("<a href=\"/mrrp\" title=\"a &amp; b\">meow</a> <img src=\"/purr.png\" alt=\"nya\" title=\"hiss\"/>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn link_parentheses() {
+            let p = make_parser(&[]);
+            let res = p.parse("[Rust](https://en.wikipedia.org/wiki/Rust_(programming_language)) ([meow](/mrrp))").into_result().unwrap();
+            let expected = format!("<a href=\"https://en.wikipedia.org/wiki/Rust_(programming_language)\">Rust</a> (<a href=\"/mrrp\">meow</a>)");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn nested_link_text() {
+            let p = make_parser(&[]);
+            let res = p.parse("[**meow** ![mrrp](/nya.png)](/purr)").into_result().unwrap();
+            let expected = format!("<a href=\"/purr\"><b>meow</b> <img src=\"/nya.png\" alt=\"mrrp\"/></a>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn autolink() {
+            let p = make_parser(&[]);
+            let res = p.parse("<https://meow.cat/?a=b> <mrrp@meow.cat> <span>").into_result().unwrap();
+            let expected = format!("<a href=\"https://meow.cat/?a=b\">https://meow.cat/?a=b</a> <a href=\"mailto:mrrp@meow.cat\">mrrp@meow.cat</a> <span>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn bare_urls() {
+            let p = MarkdownParser::default().link_urls();
+            let (res, _) = p.render(&Tera::default(), "see https://meow.cat/a_b, *www.mrrp.cat* and `https://nya.cat`").unwrap();
+            let expected = format!("<p>see <a href=\"https://meow.cat/a_b\">https://meow.cat/a_b</a>, <i><a href=\"http://www.mrrp.cat\">www.mrrp.cat</a></i> and <code>https:&#x2F;&#x2F;nya.cat</code></p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn reference_links() {
+            let p = MarkdownParser::default();
+            let (res, _) = p.render(&Tera::default(), "[meow][Cat  Site], [mrrp][] and [mrrp] but not [nya]\n\n[cat site]: https://meow.cat \"Meow\"\n[mrrp]: <https://mrrp.cat>\n```\n[purr]: /hiss\n```").unwrap();
+            let expected = format!("<p><a href=\"https://meow.cat\" title=\"Meow\">meow</a>, <a href=\"https://mrrp.cat\">mrrp</a> and <a href=\"https://mrrp.cat\">mrrp</a> but not [nya]</p><pre><code>[purr]: &#x2F;hiss\n</code></pre>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn code_block() {
+            let p = make_parser(&[]);
+            let res = p.parse("```meow```").into_result().unwrap();
+            let expected = format!("<pre><code>meow</code></pre>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn highlight_classes() {
+            let p = MarkdownParser::default().highlight_classes();
+            let (res, _) = p.render(&Tera::default(), "```rust\nlet a = b < c;\n```").unwrap();
+            let expected = format!("<pre><code class=\"language-rust\"><span class=\"source rust\"><span class=\"storage type rust\">let</span> a <span class=\"keyword operator rust\">=</span> b <span class=\"keyword operator rust\">&lt;</span> c<span class=\"punctuation terminator rust\">;</span>\n</span></code></pre>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn highlight_inline() {
+            let p = MarkdownParser::default().highlight("InspiredGitHub");
+            let (res, _) = p.render(&Tera::default(), "```rust\nlet a = b;\n```").unwrap();
+            let expected = format!("<pre style=\"background-color: #ffffff;\"><code class=\"language-rust\"><span style=\"font-weight:bold;color:#a71d5d;\">let</span><span style=\"color:#323232;\"> a </span><span style=\"font-weight:bold;color:#a71d5d;\">=</span><span style=\"color:#323232;\"> b;\n</span></code></pre>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn highlight_unknown_language() {
+            let p = MarkdownParser::default().highlight_classes();
+            let (res, _) = p.render(&Tera::default(), "```meowlang\n<meow>\n```").unwrap();
+            let expected = format!("<pre><code class=\"language-meowlang\">&lt;meow&gt;\n</code></pre>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn code_block_escaping() {
+            let p = make_parser(&[]);
+            let res = p.parse("```meow\"><b>.mrrp<i>\n<nya> & \"purr\"\n```").into_result().unwrap();
+            let expected = format!("<pre><small>meow&quot;&gt;&lt;b&gt;.mrrp&lt;i&gt;</small><code class=\"language-mrrp&lt;i&gt;\">&lt;nya&gt; &amp; &quot;purr&quot;\n</code></pre>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn text_escaping() {
+            let p = make_parser(&[]);
+            let res = p.parse("1 < 2 && 3 > 2 &copy; &#169; &#xA9; \\<b>").into_result().unwrap();
+            let expected = format!("1 &lt; 2 &amp;&amp; 3 &gt; 2 &copy; &#169; &#xA9; &lt;b&gt;");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn attribute_escaping() {
+            let p = make_parser(&[]);
+            let res = p.parse("[meow](/mrrp\"nya&purr) ![a \"*hiss*\" & &amp;](/meow.png)").into_result().unwrap();
+            let expected = format!("<a href=\"/mrrp&quot;nya&amp;purr\">meow</a> <img src=\"/meow.png\" alt=\"a &quot;hiss&quot; &amp; &amp;\"/>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn code_line() {
+            let p = make_parser(&[]);
+            let res = p.parse("`meow`").into_result().unwrap();
+            let expected = format!("<code>meow</code>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn bold() {
+            let p = make_parser(&[]);
+            let res = p.parse("**meow**").into_result().unwrap();
+            let expected = format!("<b>meow</b>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn italic() {
+            let p = make_parser(&[]);
+            let res = p.parse("*meow*").into_result().unwrap();
+            let expected = format!("<i>meow</i>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn bold_and_italic() {
+            let p = make_parser(&[]);
+            let res = p.parse("***meow***").into_result().unwrap();
+            let expected = format!("<b><i>meow</i></b>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn strikethrough() {
+            let p = make_parser(&[]);
+            let res = p.parse("~~meow~~").into_result().unwrap();
+            let expected = format!("<s>meow</s>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn underline() {
+            let p = make_parser(&[]);
+            let res = p.parse("__meow__").into_result().unwrap();
+            let expected = format!("<u>meow</u>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn escape() {
+            let p = make_parser(&[]);
+            let res = p.parse("meow \\*mrrp\\*").into_result().unwrap();
+            let expected = format!("meow *mrrp*");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn inline_math() {
+            let p = make_parser(&[]);
+            let res = p.parse("meow $x^2 < y$").into_result().unwrap();
+            let expected = format!("meow <math><msup><mi>x</mi><mn>2</mn></msup><mo>&lt;</mo><mi>y</mi></math>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn display_math() {
+            let p = make_parser(&[]);
+            let res = p.parse("meow\n$$\n\\frac{a}{b}\n$$\nmrrp").into_result().unwrap();
+            let expected = format!("<p>meow</p><math display=\"block\"><mfrac><mrow><mi>a</mi></mrow><mrow><mi>b</mi></mrow></mfrac></math><p>mrrp</p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[actix_web::test]
+        async fn unsupported_math() {
+            let p = MarkdownParser::default();
+            let res = p.process(&mut State::new("dist", "test/templates").unwrap(), &Item {
+                path: "meow.md".into(),
+                bytes: b"---\ntitle: meow\n---\nmeow $x$\n\n$$\na \\meow b\n$$".to_vec(),
+                properties: HashMap::new(),
+            }).await.unwrap_err();
+            let expected = format!("Failed to parse markdown in meow.md\nmeow.md:7:3: Unsupported command \\meow\n  |\n7 | a \\meow b\n  |   ^^^^^");
+
+            assert_eq!(expected, res.to_string());
+        }
+
+        #[test]
+        fn literal_dollars() {
+            let p = make_parser(&[]);
+            let res = p.parse("`$x$` costs \\$5, $5 or $ 10 $").into_result().unwrap();
+            let expected = format!("<code>$x$</code> costs $5, $5 or $ 10 $");
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    mod ast {
+        use tera::Tera;
+
+        use crate::parser::{markdown::{ast::{self, Block, Inline}, MarkdownParser}, ParserProcedure};
+
+        #[test]
+        fn parse() {
+            let p = MarkdownParser::default();
+            let blocks = p.parse("# meow\n\n[**mrrp**](/nya)").unwrap();
+
+            let expected = vec![
+                Block::Heading(1, Some(format!("meow")), vec![Inline::Text(format!("meow"))]),
+                Block::Paragraph(vec![Inline::Link(format!("/nya"), None, vec![Inline::Bold(vec![Inline::Text(format!("mrrp"))])])]),
+            ];
+
+            assert_eq!(expected, blocks);
+        }
+
+        #[test]
+        fn transform() {
+            let p = MarkdownParser::default()
+                .transform(|blocks| ast::visit_blocks(blocks, &mut |block| if let Block::Heading(level, _, _) = block {
+                    *level = (*level + 1).min(6);
+                }))
+                .transform(|blocks| ast::visit_inlines(blocks, &mut |inline| if let Inline::Link(href, _, _) = inline {
+                    *href = format!("https://example.com{}", href);
+                }));
+            let (res, _) = p.render(&Tera::default(), "# meow\n\n- [mrrp](/nya)[^1]\n\n[^1]: [purr](/hiss)").unwrap();
+            let expected = format!("<h2 id=\"meow\">meow</h2><ul><li><a href=\"https://example.com/nya\">mrrp</a><sup class=\"footnote-ref\" id=\"fnref-1\"><a href=\"#fn-1\">1</a></sup></li></ul><section class=\"footnotes\"><ol><li id=\"fn-1\"><p><a href=\"https://example.com/hiss\">purr</a> <a href=\"#fnref-1\" class=\"footnote-backref\">↩</a></p></li></ol></section>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn plain_text() {
+            let p = MarkdownParser::default();
+            let blocks = p.parse("# meow\n\n**mrrp** [nya](/nya)\nand `purr`[^1]\n\n---\n\n- hiss\n- ![a cat](/cat.png)").unwrap();
+            let expected = format!("meow\n\nmrrp nya and purr\n\nhiss\na cat");
+
+            assert_eq!(expected, ast::text(&blocks));
+        }
+    }
+
+    mod summary {
+        use serde_json::json;
+        use tera::Tera;
+
+        use crate::parser::{markdown::MarkdownParser, ParserProcedure};
+
+        #[test]
+        fn more_marker() {
+            let p = MarkdownParser::default().summarize(2);
+            let (res, properties) = p.render(&Tera::default(), "# meow\n\nmrrp nya[^1]\n<!-- more -->\npurr\n\n[^1]: hiss").unwrap();
+
+            assert!(res.contains("<p>mrrp nya<sup class=\"footnote-ref\" id=\"fnref-1\"><a href=\"#fn-1\">1</a></sup></p><!-- more --><p>purr</p>"));
+            assert_eq!(json!("<h1 id=\"meow\">meow</h1><p>mrrp nya</p>"), properties["summary"]);
+        }
+
+        #[test]
+        fn word_limit() {
+            let p = MarkdownParser::default().summarize(3);
+            let (_, properties) = p.render(&Tera::default(), "meow\n\nmrrp **nya purr** hiss\n\nmeow").unwrap();
+
+            assert_eq!(json!("<p>meow</p><p>mrrp <b>nya</b>…</p>"), properties["summary"]);
+        }
+
+        #[test]
+        fn word_limit_list() {
+            let p = MarkdownParser::default().summarize(4);
+            let (_, properties) = p.render(&Tera::default(), "meow\n\n- mrrp nya\n- purr hiss meow\n- nya\n\nmrrp").unwrap();
+
+            assert_eq!(json!("<p>meow</p><ul><li>mrrp nya</li><li>purr…</li></ul>"), properties["summary"]);
+
+            let (_, properties) = p.render(&Tera::default(), "1. meow mrrp nya purr hiss\n2. meow").unwrap();
+
+            assert_eq!(json!("<ol><li>meow mrrp nya purr…</li></ol>"), properties["summary"]);
+        }
+
+        #[test]
+        fn word_limit_images() {
+            let p = MarkdownParser::default().summarize(3);
+            let (_, properties) = p.render(&Tera::default(), "meow ![](a.png) $x + y$ mrrp\n\n![nya purr hiss](b.png)").unwrap();
+
+            assert_eq!(json!("<p>meow <img src=\"a.png\" alt=\"\"/> <math><mi>x</mi><mo>+</mo><mi>y</mi></math>…</p>"), properties["summary"]);
+
+            let (_, properties) = p.render(&Tera::default(), "meow mrrp\n\n![nya purr hiss](b.png)").unwrap();
+
+            assert_eq!(json!("<p>meow mrrp</p><p><img src=\"b.png\" alt=\"nya purr hiss\"/></p>"), properties["summary"]);
+        }
+
+        #[test]
+        fn finished() {
+            let p = MarkdownParser::default().summarize(50).heading_anchors().highlight_classes();
+            let (res, properties) = p.render(&Tera::default(), "## meow\n\n```rust\nlet a;\n```\n<!-- more -->\npurr").unwrap();
+            let summary = properties["summary"].as_str().unwrap();
+
+            assert!(res.starts_with(summary));
+            assert!(summary.contains("<h2 id=\"meow\">meow <a class=\"anchor\" href=\"#meow\" aria-hidden=\"true\">#</a></h2>"));
+            assert!(summary.contains("<span class=\"storage type rust\">let</span>"));
+        }
+
+        #[test]
+        fn plain_text() {
+            let p = MarkdownParser::default().summarize(50);
+            let (_, properties) = p.render(&Tera::default(), &"meow *mrrp* ".repeat(150)).unwrap();
+
+            assert_eq!(json!("meow mrrp ".repeat(150).trim()), properties["plain_text"]);
+            assert_eq!(json!(300), properties["word_count"]);
+            assert_eq!(json!(2), properties["reading_time"]);
+        }
+
+        #[test]
+        fn disabled() {
+            let p = MarkdownParser::default();
+            let (_, properties) = p.render(&Tera::default(), "meow").unwrap();
+
+            assert!(!properties.contains_key("summary"));
+        }
+    }
+
+    mod html {
+        use tera::Tera;
+
+        use crate::parser::{markdown::{html::{Allowlist, RawHtml}, MarkdownParser}, ParserProcedure};
+
+        #[test]
+        fn verbatim() {
+            let p = MarkdownParser::default();
+            let (res, _) = p.render(&Tera::default(), "<div class=\"meow\">\n**mrrp**\n</div>\n\nnya <kbd>purr</kbd> 1 < 2").unwrap();
+            let expected = format!("<div class=\"meow\">\n**mrrp**\n</div><p>nya <kbd>purr</kbd> 1 &lt; 2</p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn escape() {
+            let p = MarkdownParser::default().raw_html(RawHtml::Escape);
+            let (res, _) = p.render(&Tera::default(), "<div>\nmeow\n</div>\n\nmrrp <b>nya</b>\n\n<!-- more -->").unwrap();
+            let expected = format!("<p>&lt;div&gt;\nmeow\n&lt;/div&gt;</p><p>mrrp &lt;b&gt;nya&lt;/b&gt;</p><!-- more -->");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn escape_comments() {
+            let p = MarkdownParser::default().raw_html(RawHtml::Escape);
+            let (res, _) = p.render(&Tera::default(), "meow <!-- --!><script>mrrp()</script> -->\n\n<!-- nya --!><script>purr()</script> -->\n\n<!-- more -->").unwrap();
+            let expected = format!("<p>meow &lt;!-- --!&gt;&lt;script&gt;mrrp()&lt;/script&gt; --&gt;</p><p>&lt;!-- nya --!&gt;&lt;script&gt;purr()&lt;/script&gt; --&gt;</p><!-- more -->");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn sanitize() {
+            let p = MarkdownParser::default().raw_html(RawHtml::Sanitize(Allowlist::default()));
+            let (res, _) = p.render(&Tera::default(), "<div onclick=\"meow()\"><script>mrrp()</script><a href=\"java&#115;cript:nya()\" title='purr'>hiss</a></div>\n\n<span style=x>meow</span> <iframe src=\"/mrrp\"></iframe> [nya](javascript:purr) [hiss](/meow)").unwrap();
+            let expected = format!("<div><a title=\"purr\">hiss</a></div><p><span>meow</span>  nya <a href=\"/meow\">hiss</a></p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn sanitize_unbalanced() {
+            let p = MarkdownParser::default().raw_html(RawHtml::Sanitize(Allowlist::default()));
+            let (res, _) = p.render(&Tera::default(), "</div></main><p>meow\n\nmrrp <b>nya <i>purr</b> *hiss <u>meow*").unwrap();
+            let expected = format!("<p>meow</p><p>mrrp <b>nya <i>purr</i></b> <i>hiss <u>meow</u></i></p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn sanitize_comments() {
+            let p = MarkdownParser::default().raw_html(RawHtml::Sanitize(Allowlist::empty()));
+            let (res, _) = p.render(&Tera::default(), "meow <!-- --!><script>mrrp()</script> -->\n\n<!-- more -->").unwrap();
+            let expected = format!("<p>meow </p><!-- more -->");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn custom_allowlist() {
+            let p = MarkdownParser::default().raw_html(RawHtml::Sanitize(Allowlist::empty().tag("video", &["src"]).scheme("https")));
+            let (res, _) = p.render(&Tera::default(), "meow <video src=\"https://meow.cat/mrrp.mp4\" controls></video> <b>nya</b>").unwrap();
+            let expected = format!("<p>meow <video src=\"https://meow.cat/mrrp.mp4\"></video> nya</p>");
+
+            assert_eq!(expected, res);
+        }
+    }
+
+    mod typography {
+        use std::collections::HashMap;
+
+        use tera::Tera;
+
+        use crate::{data::{Item, State}, parser::{markdown::MarkdownParser, ParserProcedure}};
+
+        #[test]
+        fn english() {
+            let p = MarkdownParser::default().smart_punctuation("en");
+            let (res, _) = p.render(&Tera::default(), "\"Meow,\" said the cat's 'friend' -- twice --- and then...\n\n\"**mrrp**\" `\"nya\" -- 'purr'...`").unwrap();
+            let expected = format!("<p>“Meow,” said the cat’s ‘friend’ – twice — and then…</p><p>“<b>mrrp</b>” <code>&quot;nya&quot; -- &#x27;purr&#x27;...</code></p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn apostrophes() {
+            let p = MarkdownParser::default().smart_punctuation("en");
+            let (res, _) = p.render(&Tera::default(), "rock 'n' roll, the '90s, \"nested 'quotes' here\" and 'single' ones").unwrap();
+            let expected = format!("<p>rock ’n’ roll, the ’90s, “nested ‘quotes’ here” and ‘single’ ones</p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn urls() {
+            let p = MarkdownParser::default().smart_punctuation("en").link_urls();
+            let (res, _) = p.render(&Tera::default(), "see https://meow.cat/a--b... and <https://mrrp.cat/'nya'> or [\"purr\"](https://hiss.cat/--)").unwrap();
+            let expected = format!("<p>see <a href=\"https://meow.cat/a--b\">https://meow.cat/a--b</a>… and <a href=\"https://mrrp.cat/'nya'\">https://mrrp.cat/'nya'</a> or <a href=\"https://hiss.cat/--\">“purr”</a></p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[test]
+        fn french() {
+            let p = MarkdownParser::default().smart_punctuation("fr-CA");
+            let (res, _) = p.render(&Tera::default(), "Il a dit \"miaou\" puis \" ronron \" et l'a 'répété'.").unwrap();
+            let expected = format!("<p>Il a dit «\u{a0}miaou\u{a0}» puis «\u{a0}ronron\u{a0}» et l’a “répété”.</p>");
+
+            assert_eq!(expected, res);
+        }
+
+        #[actix_web::test]
+        async fn page_language() {
+            let p = MarkdownParser::default().smart_punctuation("en");
+            let res = p.process(&mut State::new("dist", "test/templates").unwrap(), &Item {
+                path: "meow.md".into(),
+                bytes: b"---\nlang: de\n---\n\"Miau\" und 'schnurr'".to_vec(),
+                properties: HashMap::new(),
+            }).await.unwrap();
+
+            assert_eq!("<p>„Miau“ und ‚schnurr‘</p>", String::from_utf8(res.bytes).unwrap());
+        }
+
+        #[test]
+        fn disabled() {
+            let p = MarkdownParser::default();
+            let (res, _) = p.render(&Tera::default(), "\"meow\" -- mrrp...").unwrap();
+            let expected = format!("<p>\"meow\" -- mrrp...</p>");
 
             assert_eq!(expected, res);
         }
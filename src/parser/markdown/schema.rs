@@ -0,0 +1,331 @@
+use std::{collections::HashMap, sync::{Arc, LazyLock}};
+
+use regex::Regex;
+use serde::{de::DeserializeOwned, Serialize};
+use tera::Value;
+
+use crate::{error::FrontmatterError, procedure};
+
+static NAMED_FIELD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^(missing|unknown) field `([^`]*)`").unwrap());
+
+#[derive(Clone)]
+pub enum FieldType {
+    Any,
+    String,
+    Integer,
+    // integers are accepted too
+    Float,
+    Bool,
+    // a `yyyy-mm-dd` string, optionally followed by a time
+    Date,
+    List(Box<FieldType>),
+    Map,
+}
+
+#[derive(Clone)]
+struct Field {
+    name: String,
+    kind: FieldType,
+    required: bool,
+    default: Option<Value>,
+}
+
+// checks a field's name and what was wrong with it, also filling in defaults
+type Check = Arc<dyn Fn(&mut HashMap<String, Value>) -> Result<(), (String, String)>>;
+
+#[derive(Clone)]
+pub struct Schema {
+    check: Check,
+}
+
+impl Schema {
+    // a declarative schema, built up with `required`, `optional` and `default`
+    pub fn fields() -> FieldList {
+        FieldList {
+            fields: Vec::new(),
+            allow_unknown: false,
+        }
+    }
+
+    // deserializes the frontmatter into `T` and uses what it serializes back to, so `#[serde(default)]` values end up in the properties
+    // `#[serde(deny_unknown_fields)]` is what catches misspelled fields
+    pub fn typed<T: DeserializeOwned + Serialize + 'static>() -> Self {
+        Self {
+            check: Arc::new(|headers| {
+                // pretty printed, so the line of an error says which field it's in
+                let json = serde_json::to_string_pretty(headers).unwrap_or_default();
+                let typed = serde_json::from_str::<T>(&json).map_err(|e| serde_error(&e, &json))?;
+
+                if let Ok(Value::Object(typed)) = serde_json::to_value(typed) {
+                    *headers = typed.into_iter().collect();
+                }
+
+                Ok(())
+            }),
+        }
+    }
+
+    pub(super) fn validate(&self, file: &str, headers: &mut HashMap<String, Value>) -> Result<(), FrontmatterError> {
+        (self.check)(headers).map_err(|(field, reason)| FrontmatterError::Invalid {
+            file: file.to_owned(),
+            field,
+            reason,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct FieldList {
+    fields: Vec<Field>,
+    allow_unknown: bool,
+}
+
+impl FieldList {
+    pub fn required<S: Into<String>>(self, name: S, kind: FieldType) -> Self {
+        self.field(name, kind, true, None)
+    }
+
+    pub fn optional<S: Into<String>>(self, name: S, kind: FieldType) -> Self {
+        self.field(name, kind, false, None)
+    }
+
+    // an optional field that's set to `default` when it's missing
+    pub fn default<S: Into<String>, V: Into<Value>>(self, name: S, kind: FieldType, default: V) -> Self {
+        self.field(name, kind, false, Some(default.into()))
+    }
+
+    // fields not in the list are let through instead of rejected as typos
+    pub fn allow_unknown(self) -> Self {
+        Self {
+            allow_unknown: true,
+            ..self
+        }
+    }
+
+    fn field<S: Into<String>>(mut self, name: S, kind: FieldType, required: bool, default: Option<Value>) -> Self {
+        self.fields.push(Field {
+            name: name.into(),
+            kind,
+            required,
+            default,
+        });
+
+        self
+    }
+
+    fn check(&self, headers: &mut HashMap<String, Value>) -> Result<(), (String, String)> {
+        if !self.allow_unknown {
+            let mut unknown = headers
+                .keys()
+                .filter(|key| !self.fields.iter().any(|field| &field.name == *key))
+                .collect::<Vec<&String>>();
+            unknown.sort();
+
+            if let Some(key) = unknown.first() {
+                return Err((key.to_string(), "is not a known field".to_owned()));
+            }
+        }
+
+        for field in &self.fields {
+            match headers.get(&field.name) {
+                Some(value) if !matches(&field.kind, value) => {
+                    return Err((field.name.clone(), format!("should be {}, found {}", describe(&field.kind), describe_value(value))));
+                },
+                Some(_) => {},
+                None if field.required => return Err((field.name.clone(), "is required".to_owned())),
+                None => {
+                    if let Some(default) = &field.default {
+                        headers.insert(field.name.clone(), default.clone());
+                    }
+                },
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<FieldList> for Schema {
+    fn from(list: FieldList) -> Self {
+        Self {
+            check: Arc::new(move |headers| list.check(headers)),
+        }
+    }
+}
+
+fn matches(kind: &FieldType, value: &Value) -> bool {
+    match (kind, value) {
+        (FieldType::Any, _) => true,
+        (FieldType::String, Value::String(_)) => true,
+        (FieldType::Integer, Value::Number(n)) => n.is_i64() || n.is_u64(),
+        (FieldType::Float, Value::Number(_)) => true,
+        (FieldType::Bool, Value::Bool(_)) => true,
+        (FieldType::Date, Value::String(s)) => is_date(s),
+        (FieldType::List(kind), Value::Array(values)) => values.iter().all(|value| matches(kind, value)),
+        (FieldType::Map, Value::Object(_)) => true,
+        _ => false,
+    }
+}
+
+// the dates that sorting by a property understands
+fn is_date(s: &str) -> bool {
+    procedure::timestamp(s).is_some()
+}
+
+fn describe(kind: &FieldType) -> String {
+    match kind {
+        FieldType::Any => "anything".to_owned(),
+        FieldType::String => "a string".to_owned(),
+        FieldType::Integer => "an integer".to_owned(),
+        FieldType::Float => "a number".to_owned(),
+        FieldType::Bool => "a boolean".to_owned(),
+        FieldType::Date => "a date".to_owned(),
+        FieldType::List(kind) => format!("a list of {}", plural(kind)),
+        FieldType::Map => "a map".to_owned(),
+    }
+}
+
+fn plural(kind: &FieldType) -> String {
+    match kind {
+        FieldType::Any => "anything".to_owned(),
+        FieldType::List(kind) => format!("lists of {}", plural(kind)),
+        kind => format!("{}s", describe(kind).trim_start_matches("a ").trim_start_matches("an ")),
+    }
+}
+
+fn describe_value(value: &Value) -> String {
+    match value {
+        Value::Null => "nothing".to_owned(),
+        Value::Bool(_) => "a boolean".to_owned(),
+        Value::Number(_) => "a number".to_owned(),
+        Value::String(s) => format!("the string \"{}\"", s),
+        Value::Array(_) => "a list".to_owned(),
+        Value::Object(_) => "a map".to_owned(),
+    }
+}
+
+// serde names missing and unknown fields itself, anything else is found from the top-level key on or above the error's line
+fn serde_error(e: &serde_json::Error, json: &str) -> (String, String) {
+    let message = e.to_string();
+    let message = match message.rfind(" at line ") {
+        Some(i) => &message[..i],
+        None => &message,
+    };
+
+    if let Some(captures) = NAMED_FIELD.captures(message) {
+        let reason = if &captures[1] == "missing" { "is required" } else { "is not a known field" };
+
+        return (captures[2].to_owned(), reason.to_owned());
+    }
+
+    let field = json
+        .lines()
+        .take(e.line())
+        .filter_map(|line| line.strip_prefix("  \""))
+        .filter_map(|line| line.split_once("\":"))
+        .last()
+        .map(|(key, _)| key.to_owned())
+        .unwrap_or_default();
+
+    (field, message.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use tera::Value;
+
+    use super::{FieldType, Schema};
+
+    fn headers(value: Value) -> HashMap<String, Value> {
+        serde_json::from_value(value).unwrap()
+    }
+
+    fn fields() -> Schema {
+        Schema::fields()
+            .required("title", FieldType::String)
+            .optional("date", FieldType::Date)
+            .optional("tags", FieldType::List(Box::new(FieldType::String)))
+            .default("draft", FieldType::Bool, false)
+            .into()
+    }
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Page {
+        title: String,
+        #[serde(default)]
+        weight: i64,
+        tags: Option<Vec<String>>,
+    }
+
+    #[test]
+    fn defaults() {
+        let mut h = headers(json!({ "title": "meow", "date": "2024-01-02", "tags": ["mrrp"] }));
+
+        fields().validate("meow.md", &mut h).unwrap();
+
+        assert_eq!(json!(false), h["draft"]);
+    }
+
+    #[test]
+    fn field_errors() {
+        let error = fields().validate("meow.md", &mut headers(json!({ "tittle": "meow" }))).unwrap_err();
+        assert_eq!("Invalid frontmatter in meow.md: `tittle` is not a known field", error.to_string());
+
+        let error = fields().validate("meow.md", &mut headers(json!({ "date": "2024-01-02" }))).unwrap_err();
+        assert_eq!("Invalid frontmatter in meow.md: `title` is required", error.to_string());
+
+        let error = fields().validate("meow.md", &mut headers(json!({ "title": "meow", "date": "yesterday" }))).unwrap_err();
+        assert_eq!("Invalid frontmatter in meow.md: `date` should be a date, found the string \"yesterday\"", error.to_string());
+
+        fields().validate("meow.md", &mut headers(json!({ "title": "meow", "date": "2024-01-02T03:04:05+01:00" }))).unwrap();
+        fields().validate("meow.md", &mut headers(json!({ "title": "meow", "date": "2024-01-02 03:04" }))).unwrap();
+        let error = fields().validate("meow.md", &mut headers(json!({ "title": "meow", "date": "2024-01-02T99:99" }))).unwrap_err();
+        assert_eq!("Invalid frontmatter in meow.md: `date` should be a date, found the string \"2024-01-02T99:99\"", error.to_string());
+        let error = fields().validate("meow.md", &mut headers(json!({ "title": "meow", "date": "2024-01-02 meow" }))).unwrap_err();
+        assert_eq!("Invalid frontmatter in meow.md: `date` should be a date, found the string \"2024-01-02 meow\"", error.to_string());
+
+        let error = fields().validate("meow.md", &mut headers(json!({ "title": "meow", "tags": ["mrrp", 1] }))).unwrap_err();
+        assert_eq!("Invalid frontmatter in meow.md: `tags` should be a list of strings, found a list", error.to_string());
+    }
+
+    #[test]
+    fn allow_unknown() {
+        let schema: Schema = Schema::fields()
+            .optional("title", FieldType::String)
+            .allow_unknown()
+            .into();
+
+        schema.validate("meow.md", &mut headers(json!({ "mrrp": 1 }))).unwrap();
+    }
+
+    #[test]
+    fn typed() {
+        let mut h = headers(json!({ "title": "meow", "tags": ["mrrp"] }));
+
+        Schema::typed::<Page>().validate("meow.md", &mut h).unwrap();
+
+        assert_eq!(json!(0), h["weight"]);
+    }
+
+    #[test]
+    fn typed_errors() {
+        let schema = Schema::typed::<Page>();
+
+        let error = schema.validate("meow.md", &mut headers(json!({ "title": "meow", "tittle": "mrrp" }))).unwrap_err();
+        assert_eq!("Invalid frontmatter in meow.md: `tittle` is not a known field", error.to_string());
+
+        let error = schema.validate("meow.md", &mut headers(json!({ "weight": 1 }))).unwrap_err();
+        assert_eq!("Invalid frontmatter in meow.md: `title` is required", error.to_string());
+
+        let error = schema.validate("meow.md", &mut headers(json!({ "title": "meow", "weight": "heavy", "tags": ["mrrp", "nya"] }))).unwrap_err();
+        assert_eq!("Invalid frontmatter in meow.md: `weight` invalid type: string \"heavy\", expected i64", error.to_string());
+
+        let error = schema.validate("meow.md", &mut headers(json!({ "title": "meow", "tags": ["mrrp", 2], "weight": 1 }))).unwrap_err();
+        assert_eq!("Invalid frontmatter in meow.md: `tags` invalid type: integer `2`, expected a string", error.to_string());
+    }
+}
@@ -0,0 +1,273 @@
+use std::{collections::HashSet, ops::Range, sync::LazyLock};
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use tera::{Map, Tera, Value};
+
+use super::{ast::{self, Block, Inline}, diagnostic::SourceMap};
+
+pub(super) struct Shortcode {
+    pub(super) name: String,
+    args: Map<String, Value>,
+    pub(super) inner: Option<String>,
+    pub(super) offset: usize,
+}
+
+struct Tag {
+    start: usize,
+    end: usize,
+    closing: bool,
+    name: String,
+    args: String,
+}
+
+impl Shortcode {
+    pub(super) fn render(&self, tera: &Tera, inner: Option<String>) -> Result<String> {
+        let mut context = self.args.clone();
+
+        if let Some(inner) = inner {
+            context.insert("inner".to_owned(), Value::String(inner));
+        }
+
+        tera.render(&format!("shortcodes/{}.html", self.name), &tera::Context::from_value(Value::Object(context))?)
+            .with_context(|| format!("Failed to render shortcode '{}'", self.name))
+    }
+}
+
+static TAG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)\{\{<\s*(/?)\s*([\w-]+)(.*?)>\}\}").unwrap());
+static ESCAPED: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?s)\{\{</\*(.*?)\*/>\}\}").unwrap());
+static ARG: LazyLock<Regex> = LazyLock::new(|| Regex::new(r#"([\w-]+)\s*=\s*(?:"((?:[^"\\]|\\.)*)"|(\S+))"#).unwrap());
+static PLACEHOLDER: LazyLock<Regex> = LazyLock::new(|| Regex::new("\u{E000}shortcode([0-9]+)\u{E000}").unwrap());
+
+pub(super) fn placeholder(index: usize) -> String {
+    format!("\u{E000}shortcode{}\u{E000}", index)
+}
+
+// swaps top-level shortcodes for placeholders, leaving nested ones in the inner text of their parent
+pub(super) fn extract(body: &str) -> Result<(String, Vec<Shortcode>, SourceMap)> {
+    let fences = fenced_ranges(body);
+    let spans = code_spans(body, &fences);
+    let escapes = ESCAPED
+        .find_iter(body)
+        .map(|m| m.range())
+        .collect::<Vec<_>>();
+    let tags = TAG
+        .captures_iter(body)
+        .filter_map(|captures| {
+            let whole = captures.get(0)?;
+
+            if fences.iter().chain(&spans).chain(&escapes).any(|range| range.contains(&whole.start())) {
+                return None;
+            }
+
+            Some(Tag {
+                start: whole.start(),
+                end: whole.end(),
+                closing: !captures[1].is_empty(),
+                name: captures[2].to_owned(),
+                args: captures[3].to_owned(),
+            })
+        })
+        .collect::<Vec<Tag>>();
+
+    let mut text = String::new();
+    let mut map = SourceMap::default();
+    let mut shortcodes = Vec::new();
+    let mut last = 0;
+    let mut i = 0;
+
+    while i < tags.len() {
+        let open = &tags[i];
+
+        if open.closing {
+            bail!("Closing shortcode '{}' has no matching opening shortcode", open.name);
+        }
+
+        let mut depth = 0;
+        let mut close = None;
+
+        for (j, t) in tags.iter().enumerate().skip(i + 1) {
+            if t.name != open.name {
+                continue;
+            }
+
+            if !t.closing {
+                depth += 1;
+            } else if depth == 0 {
+                close = Some(j);
+                break;
+            } else {
+                depth -= 1;
+            }
+        }
+
+        let (inner, end, next) = match close {
+            Some(j) => (Some(body[open.end..tags[j].start].trim().to_owned()), tags[j].end, j + 1),
+            None => (None, open.end, i + 1),
+        };
+        let offset = open.end + body[open.end..].len() - body[open.end..].trim_start().len();
+
+        unescape(body, last..open.start, &mut text, &mut map);
+        map.anchor(text.len(), open.start);
+        text.push_str(&placeholder(shortcodes.len()));
+        shortcodes.push(Shortcode {
+            name: open.name.clone(),
+            args: args(&open.args),
+            inner,
+            offset,
+        });
+
+        last = end;
+        i = next;
+    }
+
+    unescape(body, last..body.len(), &mut text, &mut map);
+
+    Ok((text, shortcodes, map))
+}
+
+// substitutes rendered shortcodes, unwrapping the ones that were alone in their paragraph
+pub(super) fn resolve(html: String, rendered: &[String]) -> String {
+    let mut html = html;
+
+    for (i, s) in rendered.iter().enumerate() {
+        let placeholder = placeholder(i);
+
+        html = html
+            .replace(&format!("<p>{}</p>", placeholder), s)
+            .replace(&placeholder, s);
+    }
+
+    html
+}
+
+// drops placeholders from text that shortcodes can't be rendered into
+pub(super) fn strip(text: &str) -> String {
+    PLACEHOLDER.replace_all(text, "").into_owned()
+}
+
+// the index of the last shortcode with its placeholder in `html`
+pub(super) fn last(html: &str) -> Option<usize> {
+    PLACEHOLDER
+        .captures_iter(html)
+        .filter_map(|captures| captures[1].parse().ok())
+        .max()
+}
+
+// the shortcodes sharing their text with something else, or in a heading or table cell, so rendered inside a line
+pub(super) fn inline(blocks: &mut [Block]) -> HashSet<usize> {
+    let mut alone = HashSet::new();
+    let mut inline = HashSet::new();
+
+    ast::visit_blocks(blocks, &mut |block| if let Block::Paragraph(inlines) = block {
+        if let [Inline::Text(text)] = inlines.as_slice() {
+            alone.extend(PLACEHOLDER
+                .captures(text.trim())
+                .filter(|captures| captures[0].len() == text.trim().len())
+                .and_then(|captures| captures[1].parse::<usize>().ok()));
+        }
+    });
+    ast::visit_block_inlines(blocks, &mut |inlines| inline.extend(PLACEHOLDER
+        .captures_iter(&ast::inline_text(inlines))
+        .filter_map(|captures| captures[1].parse::<usize>().ok())));
+
+    &inline - &alone
+}
+
+fn args(text: &str) -> Map<String, Value> {
+    let mut args = Map::new();
+
+    for captures in ARG.captures_iter(text) {
+        let value = match (captures.get(2), captures.get(3)) {
+            (Some(quoted), _) => Value::String(quoted.as_str().replace("\\\"", "\"")),
+            (None, Some(bare)) => serde_json::from_str(bare.as_str()).unwrap_or_else(|_| Value::String(bare.as_str().to_owned())),
+            (None, None) => continue,
+        };
+
+        args.insert(captures[1].to_owned(), value);
+    }
+
+    args
+}
+
+// copies a range of the body, turning `{{</* name */>}}` into a literal `{{< name >}}`
+fn unescape(body: &str, range: Range<usize>, text: &mut String, map: &mut SourceMap) {
+    let mut last = range.start;
+
+    for captures in ESCAPED.captures_iter(&body[range.clone()]) {
+        let whole = captures.get(0).unwrap();
+
+        map.anchor(text.len(), last);
+        text.push_str(&body[last..range.start + whole.start()]);
+        map.anchor(text.len(), range.start + whole.start());
+        text.push_str(&format!("{{{{<{}>}}}}", &captures[1]));
+        last = range.start + whole.end();
+    }
+
+    map.anchor(text.len(), last);
+    text.push_str(&body[last..range.end]);
+}
+
+// the inline code outside of `fences`, from a run of backticks to the next run of the same length in its paragraph
+fn code_spans(body: &str, fences: &[Range<usize>]) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut open: Option<Range<usize>> = None;
+    let mut i = 0;
+
+    while let Some(start) = body[i..].find('`').map(|p| i + p) {
+        let end = start + body[start..].len() - body[start..].trim_start_matches('`').len();
+        i = end;
+
+        if fences.iter().any(|fence| fence.contains(&start)) {
+            continue;
+        }
+
+        // a blank line ends the paragraph an unclosed run was in
+        let between = open.as_ref().and_then(|run| body[run.end..start].rsplit_once('\n'));
+
+        if between.is_some_and(|(lines, _)| lines.split('\n').skip(1).any(|line| line.trim().is_empty())) {
+            open = None;
+        }
+
+        match &open {
+            Some(run) if run.len() == end - start => {
+                spans.push(run.start..end);
+                open = None;
+            },
+            Some(_) => {},
+            None => open = Some(start..end),
+        }
+    }
+
+    spans
+}
+
+fn fenced_ranges(body: &str) -> Vec<Range<usize>> {
+    let mut ranges = Vec::new();
+    let mut start = None;
+    let mut offset = 0;
+
+    for line in body.split_inclusive('\n') {
+        let trimmed = line.trim();
+
+        if start.is_none() && trimmed.len() > 6 && trimmed.starts_with("```") && trimmed.ends_with("```") {
+            ranges.push(offset..offset + line.len());
+        } else if trimmed.starts_with("```") {
+            match start {
+                Some(s) => {
+                    ranges.push(s..offset + line.len());
+                    start = None;
+                },
+                None => start = Some(offset),
+            }
+        }
+
+        offset += line.len();
+    }
+
+    if let Some(s) = start {
+        ranges.push(s..body.len());
+    }
+
+    ranges
+}
@@ -0,0 +1,158 @@
+use super::ast::{self, Block, Callout, Inline, List, ListItem};
+
+pub(super) static MORE: &str = "<!-- more -->";
+
+// words per minute used for `reading_time`
+pub(super) static READING_SPEED: usize = 200;
+
+// everything before a `<!-- more -->` line, or else the first `words` words
+pub(super) fn excerpt(blocks: &[Block], mut words: usize) -> Vec<Block> {
+    let mut excerpt = match blocks.iter().position(|block| matches!(block, Block::Html(html) if html.trim() == MORE)) {
+        Some(i) => blocks[..i].to_vec(),
+        None => truncate(blocks, &mut words),
+    };
+
+    // the footnotes themselves aren't part of the excerpt
+    strip_references(&mut excerpt);
+
+    excerpt
+}
+
+// the blocks up to `remaining` words, cutting the one that goes over inside itself
+fn truncate(blocks: &[Block], remaining: &mut usize) -> Vec<Block> {
+    let mut excerpt = Vec::new();
+
+    for block in blocks {
+        if *remaining == 0 {
+            break;
+        }
+
+        let count = count(block);
+
+        if count <= *remaining {
+            excerpt.push(block.clone());
+            *remaining -= count;
+            continue;
+        }
+
+        excerpt.extend(cut(block, remaining));
+        *remaining = 0;
+    }
+
+    excerpt
+}
+
+// code, tables, math and html can't be cut, so are dropped
+fn cut(block: &Block, remaining: &mut usize) -> Option<Block> {
+    match block {
+        Block::Paragraph(inlines) => Some(Block::Paragraph(ellipsize(inlines, remaining))),
+        Block::Heading(level, id, inlines) => Some(Block::Heading(*level, id.clone(), ellipsize(inlines, remaining))),
+        // the items that fit, and the one that doesn't cut too
+        Block::List(list) => {
+            let mut items = Vec::new();
+
+            for item in &list.items {
+                if *remaining == 0 {
+                    break;
+                }
+
+                items.push(ListItem {
+                    task: item.task,
+                    blocks: truncate(&item.blocks, remaining),
+                });
+            }
+
+            Some(Block::List(List {
+                items,
+                ..list.clone()
+            }))
+        },
+        Block::Callout(callout) => Some(Block::Callout(Callout {
+            blocks: truncate(&callout.blocks, remaining),
+            ..callout.clone()
+        })),
+        _ => None,
+    }
+}
+
+fn ellipsize(inlines: &[Inline], remaining: &mut usize) -> Vec<Inline> {
+    let mut inlines = truncate_inlines(inlines, remaining);
+    inlines.push(Inline::Text("…".to_owned()));
+
+    inlines
+}
+
+// images and math count as a word each, whatever their alt text or tex
+fn count(block: &Block) -> usize {
+    if let Block::Math(_) = block {
+        return 1;
+    }
+
+    let mut blocks = [block.clone()];
+    ast::visit_inlines(&mut blocks, &mut |inline| {
+        if let Inline::Image(..) | Inline::Math(..) = inline {
+            *inline = Inline::Text(" - ".to_owned());
+        }
+    });
+
+    ast::text(&blocks).split_whitespace().count()
+}
+
+fn truncate_inlines(inlines: &[Inline], remaining: &mut usize) -> Vec<Inline> {
+    let mut kept = Vec::new();
+
+    for inline in inlines {
+        if *remaining == 0 {
+            break;
+        }
+
+        kept.push(match inline {
+            Inline::Text(text) => Inline::Text(take_words(text, remaining)),
+            Inline::Code(code) => Inline::Code(take_words(code, remaining)),
+            Inline::Bold(inner) => Inline::Bold(truncate_inlines(inner, remaining)),
+            Inline::Italic(inner) => Inline::Italic(truncate_inlines(inner, remaining)),
+            Inline::Strikethrough(inner) => Inline::Strikethrough(truncate_inlines(inner, remaining)),
+            Inline::Underline(inner) => Inline::Underline(truncate_inlines(inner, remaining)),
+            Inline::Link(href, title, inner) => Inline::Link(href.clone(), title.clone(), truncate_inlines(inner, remaining)),
+            Inline::Extension(extension, inner) => Inline::Extension(extension.clone(), truncate_inlines(inner, remaining)),
+            Inline::Image(..) | Inline::Math(..) => {
+                *remaining -= 1;
+                inline.clone()
+            },
+            inline => inline.clone(),
+        });
+    }
+
+    kept
+}
+
+fn take_words(text: &str, remaining: &mut usize) -> String {
+    let words = text.split_whitespace().collect::<Vec<&str>>();
+
+    if words.len() < *remaining {
+        *remaining -= words.len();
+
+        return text.to_owned();
+    }
+
+    let last = words[*remaining - 1];
+    *remaining = 0;
+
+    text[..last.as_ptr() as usize - text.as_ptr() as usize + last.len()].to_owned()
+}
+
+fn strip_references(blocks: &mut [Block]) {
+    let keep = |inline: &Inline| !matches!(inline, Inline::FootnoteReference(..));
+
+    ast::visit_block_inlines(blocks, &mut |inlines| inlines.retain(keep));
+    ast::visit_inlines(blocks, &mut |inline| match inline {
+        Inline::Bold(inner)
+        | Inline::Italic(inner)
+        | Inline::Strikethrough(inner)
+        | Inline::Underline(inner)
+        | Inline::Link(_, _, inner)
+        | Inline::Image(_, _, inner)
+        | Inline::Extension(_, inner) => inner.retain(keep),
+        _ => {},
+    });
+}
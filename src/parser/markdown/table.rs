@@ -0,0 +1,142 @@
+use chumsky::{prelude::*, text::newline};
+
+use crate::parser::Extra;
+
+use super::{ast::{Alignment, Block, Inline, Table}, diagnostic::{self, SourceMap}, inline, Syntax};
+
+pub(super) fn table<'src>(syntax: Syntax) -> impl Parser<'src, &'src str, Block, Extra<'src>> + Clone {
+    let line = any()
+        .and_is(newline().not())
+        .repeated()
+        .at_least(1)
+        .to_slice();
+    let padding = one_of(" \t").repeated();
+    let delimiter_cell = padding
+        .then(just(':').or_not())
+        .then(just('-').repeated().at_least(1))
+        .then(just(':').or_not())
+        .then(padding);
+    let delimiter_row = just('|')
+        .or_not()
+        .then(delimiter_cell.separated_by(just('|')).at_least(1))
+        .then(just('|').or_not())
+        .then(padding)
+        .then(line_end());
+    let interrupt = choice((
+        just("#").ignored(),
+        just(">").ignored(),
+        just("```").ignored(),
+        just("-#").ignored(),
+    ));
+
+    line
+        .filter(|s: &&str| s.contains('|'))
+        .then_ignore(newline())
+        .then_ignore(delimiter_row.rewind())
+        .then(line)
+        // a header with a different number of cells than the delimiter row isn't a table
+        .filter(|(header, delimiter): &(&str, &str)| cells(header).len() == cells(delimiter).len())
+        .then(newline()
+            .ignore_then(interrupt.not())
+            .ignore_then(line)
+            .repeated()
+            .collect::<Vec<&str>>())
+        .validate(move |((header, delimiter), rows), e, emitter| {
+            let mut errors = Vec::new();
+            let table = build(e.slice(), header, delimiter, rows, &syntax, &mut errors);
+            diagnostic::emit(&errors, e.slice(), "table", e.span(), emitter);
+
+            table
+        })
+}
+
+fn line_end<'src>() -> impl Parser<'src, &'src str, (), Extra<'src>> + Clone {
+    choice((
+        newline(),
+        end(),
+    ))
+        .rewind()
+}
+
+// errors in the cells are pushed to `errors`, at their offsets in `table`
+fn build<'a>(table: &str, header: &str, delimiter: &str, rows: Vec<&str>, syntax: &Syntax, errors: &mut Vec<Rich<'a, char>>) -> Block {
+    let offset = |line: &str| line.as_ptr() as usize - table.as_ptr() as usize;
+    let alignments = cells(delimiter)
+        .iter()
+        .map(|(cell, _)| match (cell.starts_with(':'), cell.ends_with(':')) {
+            (true, true) => Alignment::Center,
+            (true, false) => Alignment::Left,
+            (false, true) => Alignment::Right,
+            (false, false) => Alignment::None,
+        })
+        .collect::<Vec<Alignment>>();
+    let columns = alignments.len();
+
+    Block::Table(Table {
+        alignments,
+        header: row(&cells(header), columns, offset(header), syntax, errors),
+        rows: rows
+            .into_iter()
+            .map(|r| row(&cells(r), columns, offset(r), syntax, errors))
+            .collect(),
+    })
+}
+
+// pads or truncates a row to the number of columns
+fn row<'a>(cells: &[(String, SourceMap)], columns: usize, offset: usize, syntax: &Syntax, errors: &mut Vec<Rich<'a, char>>) -> Vec<Vec<Inline>> {
+    (0..columns)
+        .map(|i| match cells.get(i) {
+            Some((text, map)) if !text.is_empty() => inline(syntax.clone())
+                .parse(text)
+                .into_result()
+                .unwrap_or_else(|e| {
+                    errors.extend(e.iter().map(|e| diagnostic::relocate(e, text, "table cell", |o| offset + map.map(o))));
+
+                    Vec::new()
+                }),
+            _ => Vec::new(),
+        })
+        .collect()
+}
+
+// splits a row on unescaped pipes, turning escaped pipes into plain ones, with each cell mapped back to where it is in the row
+fn cells(line: &str) -> Vec<(String, SourceMap)> {
+    let trimmed = line.trim();
+    let trimmed = trimmed.strip_prefix('|').unwrap_or(trimmed);
+    let trimmed = match trimmed.strip_suffix('|') {
+        Some(l) if !l.ends_with('\\') => l,
+        _ => trimmed,
+    };
+    let start = trimmed.as_ptr() as usize - line.as_ptr() as usize;
+
+    let mut cells = Vec::new();
+    let mut cell = (String::new(), SourceMap::default());
+    let mut chars = trimmed.char_indices().peekable();
+
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' if chars.peek().is_some_and(|(_, c)| *c == '|') => {
+                cell.1.anchor(cell.0.len(), start + i + 1);
+                cell.0.push('|');
+                chars.next();
+            },
+            '|' => cells.push(std::mem::take(&mut cell)),
+            // leading whitespace is trimmed as the cell goes
+            c if c.is_whitespace() && cell.0.is_empty() => {},
+            c => {
+                if cell.0.is_empty() {
+                    cell.1.anchor(0, start + i);
+                }
+
+                cell.0.push(c);
+            },
+        }
+    }
+
+    cells.push(cell);
+
+    cells
+        .into_iter()
+        .map(|(cell, map)| (cell.trim_end().to_owned(), map))
+        .collect()
+}
@@ -0,0 +1,198 @@
+use std::sync::LazyLock;
+
+use regex::Regex;
+
+use super::ast::{self, Block, Inline};
+
+static URL: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"(?:[a-zA-Z][a-zA-Z0-9+.-]*://|www\.)\S+").unwrap());
+
+struct Quotes {
+    double: (char, char),
+    single: (char, char),
+    // put between guillemets and what they quote
+    padding: Option<char>,
+}
+
+static ENGLISH: Quotes = Quotes {
+    double: ('“', '”'),
+    single: ('‘', '’'),
+    padding: None,
+};
+
+static FRENCH: Quotes = Quotes {
+    double: ('«', '»'),
+    single: ('“', '”'),
+    padding: Some('\u{a0}'),
+};
+
+static GERMAN: Quotes = Quotes {
+    double: ('„', '“'),
+    single: ('‚', '‘'),
+    padding: None,
+};
+
+struct Smartener<'a> {
+    quotes: &'a Quotes,
+    // the character before the text being smartened, to tell opening quotes from closing ones
+    previous: char,
+    // whether a double or single quote is waiting to be closed
+    open: (bool, bool),
+}
+
+// curly quotes for `language`, a bcp 47 tag like `fr-CA`, along with dashes and ellipses
+pub(super) fn smarten(blocks: &mut [Block], language: &str) {
+    let quotes = match language.split(['-', '_']).next().unwrap_or_default().to_lowercase().as_str() {
+        "fr" => &FRENCH,
+        "de" => &GERMAN,
+        _ => &ENGLISH,
+    };
+    let mut smartener = Smartener {
+        quotes,
+        previous: ' ',
+        open: (false, false),
+    };
+
+    ast::visit_block_inlines(blocks, &mut |inlines| {
+        smartener.previous = ' ';
+        smartener.open = (false, false);
+        smartener.inlines(inlines);
+    });
+}
+
+impl Smartener<'_> {
+    fn inlines(&mut self, inlines: &mut [Inline]) {
+        for inline in inlines {
+            match inline {
+                Inline::Text(text) => *text = self.text(text),
+                Inline::Code(code) | Inline::Math(_, code) => self.skip(code),
+                Inline::Link(href, _, inner) => {
+                    // autolinks show their url
+                    let url = match inner.as_slice() {
+                        [Inline::Text(text)] if href.ends_with(text.as_str()) => Some(text.clone()),
+                        _ => None,
+                    };
+
+                    match url {
+                        Some(url) => self.skip(&url),
+                        None => self.inlines(inner),
+                    }
+                },
+                Inline::Bold(inner)
+                | Inline::Italic(inner)
+                | Inline::Strikethrough(inner)
+                | Inline::Underline(inner)
+                | Inline::Image(_, _, inner)
+                | Inline::Extension(_, inner) => self.inlines(inner),
+                Inline::SoftBreak | Inline::LineBreak => self.previous = ' ',
+                Inline::FootnoteReference(..) | Inline::Html(_) => {},
+            }
+        }
+    }
+
+    fn skip(&mut self, text: &str) {
+        if let Some(c) = text.chars().last() {
+            self.previous = c;
+        }
+    }
+
+    // leaves urls written in the text alone
+    fn text(&mut self, text: &str) -> String {
+        let mut smartened = String::new();
+        let mut last = 0;
+
+        let urls = URL
+            .find_iter(text)
+            .collect::<Vec<_>>();
+
+        for m in urls {
+            smartened.push_str(&self.punctuate(&text[last..m.start()]));
+            smartened.push_str(m.as_str());
+            self.skip(m.as_str());
+            last = m.end();
+        }
+
+        smartened.push_str(&self.punctuate(&text[last..]));
+
+        smartened
+    }
+
+    fn punctuate(&mut self, text: &str) -> String {
+        let chars = text.chars().collect::<Vec<char>>();
+        let mut punctuated = String::new();
+        let mut i = 0;
+
+        while i < chars.len() {
+            let c = chars[i];
+            let rest = &chars[i..];
+
+            let smart = if rest.starts_with(&['-', '-', '-']) {
+                i += 2;
+                '—'
+            } else if rest.starts_with(&['-', '-']) {
+                i += 1;
+                '–'
+            } else if rest.starts_with(&['.', '.', '.']) {
+                i += 2;
+                '…'
+            } else if c == '"' && !self.open.0 && self.opens() {
+                punctuated.push(self.quotes.double.0);
+
+                if let Some(padding) = self.quotes.padding {
+                    punctuated.push(padding);
+
+                    // the padding replaces any space already there
+                    while chars.get(i + 1).is_some_and(|c| c.is_whitespace()) {
+                        i += 1;
+                    }
+                }
+
+                self.previous = self.quotes.double.0;
+                // a single quote left open outside doesn't get closed inside
+                self.open = (true, false);
+                i += 1;
+                continue;
+            } else if c == '"' {
+                if let Some(padding) = self.quotes.padding {
+                    punctuated.truncate(punctuated.trim_end().len());
+                    punctuated.push(padding);
+                }
+
+                self.open = (false, false);
+                self.quotes.double.1
+            } else if c == '\'' && self.previous.is_alphanumeric() && chars.get(i + 1).is_some_and(|c| c.is_alphanumeric()) {
+                // an apostrophe, whatever the language's quotes look like
+                '’'
+            } else if c == '\'' && chars.get(i + 1).is_some_and(char::is_ascii_digit) {
+                // a year cut short, like `'90s`
+                '’'
+            } else if rest.starts_with(&['\'', 'n', '\'']) && !chars.get(i + 3).is_some_and(|c| c.is_alphanumeric()) {
+                // `rock 'n' roll`, where both stand for letters left out
+                punctuated.push_str("’n");
+                i += 2;
+                '’'
+            } else if c == '\'' && self.open.1 && !self.opens() {
+                self.open.1 = false;
+                self.quotes.single.1
+            } else if c == '\'' && self.opens() {
+                self.open.1 = true;
+                self.quotes.single.0
+            } else if c == '\'' {
+                '’'
+            } else {
+                c
+            };
+
+            punctuated.push(smart);
+            self.previous = smart;
+            i += 1;
+        }
+
+        punctuated
+    }
+
+    fn opens(&self) -> bool {
+        self.previous.is_whitespace()
+            || "([{<-/–—\u{a0}".contains(self.previous)
+            || [self.quotes.double.0, self.quotes.single.0].contains(&self.previous)
+    }
+}
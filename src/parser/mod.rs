@@ -11,13 +11,15 @@ pub mod image;
 pub mod html;
 pub mod css;
 
+type Extra<'src> = extra::Err<Rich<'src, char>>;
+
 #[async_trait(?Send)]
 pub trait ParserProcedure: Clone {
     fn default() -> Self;
     async fn process(&self, state: &mut State, item: &Item) -> Result<Item>;
 }
 
-fn whitespace<'src>() -> impl Parser<'src, &'src str, ()> + Clone {
+fn whitespace<'src>() -> impl Parser<'src, &'src str, (), Extra<'src>> + Clone {
     any()
         .and_is(newline().not())
         .filter(|c: &char| c.is_whitespace())
@@ -27,13 +29,13 @@ fn whitespace<'src>() -> impl Parser<'src, &'src str, ()> + Clone {
         .ignored()
 }
 
-fn line_terminator<'src>() -> impl Parser<'src, &'src str, ()> + Clone {
+fn line_terminator<'src>() -> impl Parser<'src, &'src str, (), Extra<'src>> + Clone {
     choice((
         newline(),
         end(),
     ))
 }
 
-fn fail<'src>() -> impl Parser<'src, &'src str, ()> + Clone {
+fn fail<'src>() -> impl Parser<'src, &'src str, (), Extra<'src>> + Clone {
     end().and_is(end().not())
 }
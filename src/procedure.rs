@@ -1,12 +1,19 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
 use std::marker::PhantomData;
 use std::path::Path;
+use std::sync::{Arc, OnceLock};
 use std::{env, path::PathBuf};
 
 use anyhow::{bail, Context, Result};
 use async_trait::async_trait;
+use icu_collator::{Collator, CollatorOptions};
+use icu_provider::DataLocale;
+use serde_json::Number;
 use tera::Value;
 use time::macros::format_description;
-use time::{format_description, Date};
+use time::format_description::well_known::Rfc3339;
+use time::{format_description, Date, OffsetDateTime, PrimitiveDateTime};
 use crate::data::{State};
 use crate::error::FsError;
 use crate::parser::ParserProcedure;
@@ -116,12 +123,128 @@ pub trait MultiProcedure<P: SingleProcedure>: Sized + Clone {
         }
     }
 
+    // missing properties sort last, ties are broken by path
+    fn sort_by_property<S: Into<String>>(self, key: S) -> SortByProperty<P, Self> {
+        SortByProperty {
+            p1: PhantomData::default(),
+            prior: self,
+            key: key.into(),
+            locale: None,
+            collator: Arc::default(),
+        }
+    }
+
+    // like `sort_by_property`, with strings in the order of `locale`, such as `sv` or `de-u-co-phonebk`
+    fn sort_by_property_in<S: Into<String>>(self, key: S, locale: &str) -> SortByProperty<P, Self> {
+        SortByProperty {
+            p1: PhantomData::default(),
+            prior: self,
+            key: key.into(),
+            locale: Some(locale.to_owned()),
+            collator: Arc::default(),
+        }
+    }
+
+    fn sort_by<F>(self, func: F) -> SortBy<P, Self, F>
+    where
+        F: Fn(&Item, &Item) -> Ordering + Clone,
+    {
+        SortBy {
+            p1: PhantomData::default(),
+            prior: self,
+            func,
+        }
+    }
+
+    // one page per `per_page` items, written to `path_pattern` with `{page}` replaced by the page number
+    fn paginate<S: Into<String>>(self, per_page: usize, path_pattern: S, template: &str) -> Paginate<P, Self> {
+        Paginate {
+            p1: PhantomData::default(),
+            prior: self,
+            per_page,
+            path_pattern: path_pattern.into(),
+            template: template.to_owned(),
+        }
+    }
+
+    // groups items by the terms in their list-valued property `key`, like `tags`
+    // the terms are registered in `state` as `taxonomies.<key>`, each with its `url`, `count` and `items`
+    fn taxonomy<S: Into<String>>(self, key: S) -> Taxonomy<P, Self> {
+        Taxonomy {
+            p1: PhantomData::default(),
+            prior: self,
+            key: key.into(),
+            index: None,
+            terms: None,
+            per_page: None,
+        }
+    }
+
+    // groups items by `func`'s key, to be rendered with `GroupBy::collect_into`
+    fn group_by<F>(self, func: F) -> GroupBy<P, Self, F>
+    where
+        F: Fn(&Item) -> String + Clone,
+    {
+        GroupBy {
+            p1: PhantomData::default(),
+            prior: self,
+            func,
+        }
+    }
+
+    // renders the whole collection into a single item, with the collection as `items`
+    fn collect_into<S: Into<PathBuf>>(self, path: S, template: &str) -> CollectInto<P, Self> {
+        CollectInto {
+            p1: PhantomData::default(),
+            prior: self,
+            path: path.into(),
+            template: template.to_owned(),
+        }
+    }
+
     fn reversed(self) -> Reverse<P, Self> {
         Reverse {
             p1: PhantomData::default(),
             prior: self,
         }
     }
+
+    fn filter<F>(self, func: F) -> Filter<P, Self, F>
+    where
+        F: Fn(&Item) -> bool + Clone,
+    {
+        Filter {
+            p1: PhantomData::default(),
+            prior: self,
+            func,
+        }
+    }
+
+    // keeps the items whose property `key` is equal to `value`
+    fn filter_property<S: Into<String>>(self, key: S, value: Value) -> FilterProperty<P, Self> {
+        FilterProperty {
+            p1: PhantomData::default(),
+            prior: self,
+            key: key.into(),
+            value,
+        }
+    }
+
+    fn take(self, count: usize) -> Take<P, Self> {
+        Take {
+            p1: PhantomData::default(),
+            prior: self,
+            count,
+        }
+    }
+
+    fn skip(self, count: usize) -> Skip<P, Self> {
+        Skip {
+            p1: PhantomData::default(),
+            prior: self,
+            count,
+        }
+    }
 }
 
 #[async_trait(?Send)]
@@ -328,12 +451,129 @@ pub struct SortByFilename<P: SingleProcedure, M: MultiProcedure<P>> {
 impl<P: SingleProcedure, M: MultiProcedure<P>> MultiProcedure<P> for SortByFilename<P, M> {
     async fn eval(&self, state: &mut State) -> Result<Vec<Item>> {
         let mut items = self.prior.eval(state).await?;
-        items.sort_by(|a, b| a.get_filename().unwrap_or(String::new()).cmp(&b.get_filename().unwrap_or(String::new())));
+        items.as_mut_slice().sort_by(|a, b| a.get_filename().unwrap_or(String::new()).cmp(&b.get_filename().unwrap_or(String::new())));
+
+        Ok(items)
+    }
+}
+
+#[derive(Clone)]
+pub struct SortByProperty<P: SingleProcedure, M: MultiProcedure<P>> {
+    p1: PhantomData<P>,
+    prior: M,
+    key: String,
+    // the root locale when none is given
+    locale: Option<String>,
+    // built on the first eval and kept for the ones after
+    collator: Arc<OnceLock<Collator>>,
+}
+
+impl<P: SingleProcedure, M: MultiProcedure<P>> SortByProperty<P, M> {
+    fn collator(&self) -> Result<&Collator> {
+        if let Some(collator) = self.collator.get() {
+            return Ok(collator);
+        }
+
+        let locale = match &self.locale {
+            Some(locale) => locale.parse::<DataLocale>().with_context(|| format!("Invalid locale {}", locale))?,
+            None => DataLocale::default(),
+        };
+        let collator = Collator::try_new(&locale, CollatorOptions::new())?;
+
+        Ok(self.collator.get_or_init(|| collator))
+    }
+}
+
+#[async_trait(?Send)]
+impl<P: SingleProcedure, M: MultiProcedure<P>> MultiProcedure<P> for SortByProperty<P, M> {
+    async fn eval(&self, state: &mut State) -> Result<Vec<Item>> {
+        let mut items = self.prior.eval(state).await?;
+        let collator = self.collator()?;
+
+        items.as_mut_slice().sort_by(|a, b| {
+            compare_values(collator, a.properties.get(&self.key), b.properties.get(&self.key))
+                .then_with(|| a.path.cmp(&b.path))
+        });
 
         Ok(items)
     }
 }
 
+#[derive(Clone)]
+pub struct SortBy<P, M, F>
+where
+    P: SingleProcedure,
+    M: MultiProcedure<P>,
+    F: Fn(&Item, &Item) -> Ordering + Clone,
+{
+    p1: PhantomData<P>,
+    prior: M,
+    func: F,
+}
+
+#[async_trait(?Send)]
+impl<P, M, F> MultiProcedure<P> for SortBy<P, M, F>
+where
+    P: SingleProcedure,
+    M: MultiProcedure<P>,
+    F: Fn(&Item, &Item) -> Ordering + Clone,
+{
+    async fn eval(&self, state: &mut State) -> Result<Vec<Item>> {
+        let mut items = self.prior.eval(state).await?;
+        items.as_mut_slice().sort_by(|a, b| (self.func)(a, b).then_with(|| a.path.cmp(&b.path)));
+
+        Ok(items)
+    }
+}
+
+// values of different kinds are ordered booleans, numbers, dates, strings, then anything else, with missing ones last
+fn compare_values(collator: &Collator, a: Option<&Value>, b: Option<&Value>) -> Ordering {
+    let rank = |value: Option<&Value>| match value {
+        Some(Value::Bool(_)) => 0,
+        Some(Value::Number(_)) => 1,
+        Some(Value::String(s)) if timestamp(s).is_some() => 2,
+        Some(Value::String(_)) => 3,
+        Some(Value::Array(_)) | Some(Value::Object(_)) => 4,
+        Some(Value::Null) | None => 5,
+    };
+
+    rank(a).cmp(&rank(b)).then_with(|| match (a, b) {
+        (Some(Value::Bool(a)), Some(Value::Bool(b))) => a.cmp(b),
+        (Some(Value::Number(a)), Some(Value::Number(b))) => compare_numbers(a, b),
+        (Some(Value::String(a)), Some(Value::String(b))) => match (timestamp(a), timestamp(b)) {
+            (Some(a), Some(b)) => a.cmp(&b),
+            _ => collator.compare(a, b).then_with(|| a.cmp(b)),
+        },
+        _ => Ordering::Equal,
+    })
+}
+
+// integers exactly, as f64 loses precision past 2^53, and anything else as a float
+fn compare_numbers(a: &Number, b: &Number) -> Ordering {
+    let integer = |n: &Number| n.as_i64().map(i128::from).or_else(|| n.as_u64().map(i128::from));
+
+    match (integer(a), integer(b)) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => a.as_f64().unwrap_or_default().total_cmp(&b.as_f64().unwrap_or_default()),
+    }
+}
+
+// a `yyyy-mm-dd` date, optionally with a time and offset, as nanoseconds since the epoch
+pub(crate) fn timestamp(s: &str) -> Option<i128> {
+    if let Ok(datetime) = OffsetDateTime::parse(s, &Rfc3339) {
+        return Some(datetime.unix_timestamp_nanos());
+    }
+
+    let datetime = format_description!(version = 2, "[year]-[month]-[day][first [T][t][ ]][hour]:[minute][optional [:[second][optional [.[subsecond]]]]]");
+    if let Ok(datetime) = PrimitiveDateTime::parse(s, datetime) {
+        return Some(datetime.assume_utc().unix_timestamp_nanos());
+    }
+
+    Date::parse(s, format_description!("[year]-[month]-[day]"))
+        .ok()
+        .map(|date| date.midnight().assume_utc().unix_timestamp_nanos())
+}
+
 #[derive(Clone)]
 pub struct Reverse<P: SingleProcedure, M: MultiProcedure<P>> {
     p1: PhantomData<P>,
@@ -349,3 +589,738 @@ impl<P: SingleProcedure, M: MultiProcedure<P>> MultiProcedure<P> for Reverse<P,
         Ok(items)
     }
 }
+
+#[derive(Clone)]
+pub struct Filter<P, M, F>
+where
+    P: SingleProcedure,
+    M: MultiProcedure<P>,
+    F: Fn(&Item) -> bool + Clone,
+{
+    p1: PhantomData<P>,
+    prior: M,
+    func: F,
+}
+
+#[async_trait(?Send)]
+impl<P, M, F> MultiProcedure<P> for Filter<P, M, F>
+where
+    P: SingleProcedure,
+    M: MultiProcedure<P>,
+    F: Fn(&Item) -> bool + Clone,
+{
+    async fn eval(&self, state: &mut State) -> Result<Vec<Item>> {
+        let mut items = self.prior.eval(state).await?;
+        items.retain(|item| (self.func)(item));
+
+        Ok(items)
+    }
+}
+
+#[derive(Clone)]
+pub struct FilterProperty<P: SingleProcedure, M: MultiProcedure<P>> {
+    p1: PhantomData<P>,
+    prior: M,
+    key: String,
+    value: Value,
+}
+
+#[async_trait(?Send)]
+impl<P: SingleProcedure, M: MultiProcedure<P>> MultiProcedure<P> for FilterProperty<P, M> {
+    async fn eval(&self, state: &mut State) -> Result<Vec<Item>> {
+        let mut items = self.prior.eval(state).await?;
+        items.retain(|item| item.properties.get(&self.key) == Some(&self.value));
+
+        Ok(items)
+    }
+}
+
+#[derive(Clone)]
+pub struct Take<P: SingleProcedure, M: MultiProcedure<P>> {
+    p1: PhantomData<P>,
+    prior: M,
+    count: usize,
+}
+
+#[async_trait(?Send)]
+impl<P: SingleProcedure, M: MultiProcedure<P>> MultiProcedure<P> for Take<P, M> {
+    async fn eval(&self, state: &mut State) -> Result<Vec<Item>> {
+        let mut items = self.prior.eval(state).await?;
+        items.truncate(self.count);
+
+        Ok(items)
+    }
+}
+
+#[derive(Clone)]
+pub struct Skip<P: SingleProcedure, M: MultiProcedure<P>> {
+    p1: PhantomData<P>,
+    prior: M,
+    count: usize,
+}
+
+#[async_trait(?Send)]
+impl<P: SingleProcedure, M: MultiProcedure<P>> MultiProcedure<P> for Skip<P, M> {
+    async fn eval(&self, state: &mut State) -> Result<Vec<Item>> {
+        let items = self.prior.eval(state).await?;
+
+        Ok(items.into_iter().skip(self.count).collect())
+    }
+}
+
+#[derive(Clone)]
+pub struct Paginate<P: SingleProcedure, M: MultiProcedure<P>> {
+    p1: PhantomData<P>,
+    prior: M,
+    per_page: usize,
+    path_pattern: String,
+    template: String,
+}
+
+#[async_trait(?Send)]
+impl<P: SingleProcedure, M: MultiProcedure<P>> MultiProcedure<Item> for Paginate<P, M> {
+    async fn eval(&self, state: &mut State) -> Result<Vec<Item>> {
+        if self.per_page == 0 {
+            bail!("Can't paginate with zero items per page");
+        }
+
+        let items = self.prior.eval(state).await?;
+
+        // every page would be written to the same path
+        if items.len() > self.per_page && !self.path_pattern.contains("{page}") {
+            bail!("Can't paginate {} items {} per page into {}, it has no {{page}}", items.len(), self.per_page, self.path_pattern);
+        }
+
+        render_pages(state, &items, self.per_page, &self.path_pattern, &self.template, HashMap::new())
+    }
+}
+
+// renders `items` through `template` `per_page` at a time, with `properties` added to each page
+fn render_pages(
+    state: &State,
+    items: &[Item],
+    per_page: usize,
+    path_pattern: &str,
+    template: &str,
+    properties: HashMap<String, Value>,
+) -> Result<Vec<Item>> {
+    // an empty collection still gets its first page
+    let total_pages = items.len().div_ceil(per_page).max(1);
+    let mut res = Vec::new();
+
+    for page_number in 1..=total_pages {
+        let mut properties = properties.clone();
+        properties.insert("page_number".to_owned(), Value::from(page_number));
+        properties.insert("total_pages".to_owned(), Value::from(total_pages));
+        properties.insert("prev_url".to_owned(), match page_number {
+            1 => Value::Null,
+            _ => page_url(path_pattern, page_number - 1),
+        });
+        properties.insert("next_url".to_owned(), if page_number < total_pages {
+            page_url(path_pattern, page_number + 1)
+        } else {
+            Value::Null
+        });
+
+        let page_items = items
+            .iter()
+            .skip((page_number - 1) * per_page)
+            .take(per_page)
+            .map(Item::into_meta)
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut context = properties.clone();
+        context.insert("items".to_owned(), Value::Array(page_items));
+
+        res.push(Item {
+            path: page_path(path_pattern, page_number),
+            bytes: render(state, template, context)?,
+            properties,
+        });
+    }
+
+    Ok(res)
+}
+
+// renders `template` with the site's data and `context`
+fn render(state: &State, template: &str, context: HashMap<String, Value>) -> Result<Vec<u8>> {
+    let mut properties = state.cached_data.clone();
+    properties.extend(context);
+    let ctx = tera::Context::from_serialize(properties)?;
+
+    Ok(state.tera.render(template, &ctx)?.into_bytes())
+}
+
+fn page_path(path_pattern: &str, page_number: usize) -> PathBuf {
+    PathBuf::from(path_pattern.replace("{page}", &page_number.to_string()))
+}
+
+fn page_url(path_pattern: &str, page_number: usize) -> Value {
+    Value::String(format!("/{}", page_path(path_pattern, page_number).display()))
+}
+
+#[derive(Clone)]
+pub struct Taxonomy<P: SingleProcedure, M: MultiProcedure<P>> {
+    p1: PhantomData<P>,
+    prior: M,
+    key: String,
+    index: Option<(String, String)>,
+    terms: Option<(String, String)>,
+    per_page: Option<usize>,
+}
+
+impl<P: SingleProcedure, M: MultiProcedure<P>> Taxonomy<P, M> {
+    // a page listing every term, given `taxonomy` and `terms` in its context
+    pub fn index<S: Into<String>>(self, path: S, template: &str) -> Self {
+        Self {
+            index: Some((path.into(), template.to_owned())),
+            ..self
+        }
+    }
+
+    // a page per term, written to `path_pattern` with `{term}` replaced by the term's slug
+    // `{page}` is replaced by the page number too when the term pages are paginated
+    pub fn terms<S: Into<String>>(self, path_pattern: S, template: &str) -> Self {
+        Self {
+            terms: Some((path_pattern.into(), template.to_owned())),
+            ..self
+        }
+    }
+
+    pub fn per_page(self, per_page: usize) -> Self {
+        Self {
+            per_page: Some(per_page),
+            ..self
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl<P: SingleProcedure, M: MultiProcedure<P>> MultiProcedure<Item> for Taxonomy<P, M> {
+    async fn eval(&self, state: &mut State) -> Result<Vec<Item>> {
+        if self.per_page == Some(0) {
+            bail!("Can't paginate with zero items per page");
+        }
+
+        if let Some((path_pattern, _)) = &self.terms {
+            if !path_pattern.contains("{term}") {
+                bail!("Can't write the terms of taxonomy {} to {}, it has no {{term}}", self.key, path_pattern);
+            }
+
+            if self.per_page.is_some() && !path_pattern.contains("{page}") {
+                bail!("Can't paginate the terms of taxonomy {} into {}, it has no {{page}}", self.key, path_pattern);
+            }
+        }
+
+        let items = self.prior.eval(state).await?;
+        // keyed by slug, which becomes part of the term's path
+        let mut grouped: BTreeMap<String, (String, Vec<Item>)> = BTreeMap::new();
+
+        // a single string, like `category: rust`, counts as a list of one term
+        for item in items {
+            let terms = match item.properties.get(&self.key) {
+                Some(Value::Array(terms)) => terms.clone(),
+                Some(Value::String(term)) => vec![Value::String(term.clone())],
+                _ => Vec::new(),
+            };
+
+            for term in terms.iter().filter_map(Value::as_str) {
+                let slug = slug(term);
+
+                if slug.is_empty() {
+                    bail!("Term '{}' of taxonomy {} has nothing to make a path from", term, self.key);
+                }
+
+                let (first, group) = grouped.entry(slug).or_insert_with(|| (term.to_owned(), Vec::new()));
+
+                // written differently, they'd still be written to the same path
+                if first != term {
+                    bail!("Terms '{}' and '{}' of taxonomy {} have the same slug", first, term, self.key);
+                }
+
+                // listing a term twice doesn't list the item twice
+                if !group.iter().any(|grouped| grouped.path == item.path) {
+                    group.push(item.clone());
+                }
+            }
+        }
+
+        let mut terms = tera::Map::new();
+
+        for (slug, (term, items)) in &grouped {
+            let url = match &self.terms {
+                Some((path_pattern, _)) => page_url(&path_pattern.replace("{term}", slug), 1),
+                None => Value::Null,
+            };
+            let items = items
+                .iter()
+                .map(Item::into_meta)
+                .collect::<Result<Vec<_>>>()?;
+
+            let mut info = tera::Map::new();
+            info.insert("url".to_owned(), url);
+            info.insert("count".to_owned(), Value::from(items.len()));
+            info.insert("items".to_owned(), Value::Array(items));
+            terms.insert(term.clone(), Value::Object(info));
+        }
+
+        // registered before rendering, so the taxonomy's own pages can use it as well as everything written after it
+        let mut taxonomies = match state.cached_data.remove("taxonomies") {
+            Some(Value::Object(taxonomies)) => taxonomies,
+            _ => tera::Map::new(),
+        };
+        taxonomies.insert(self.key.clone(), Value::Object(terms.clone()));
+        state.property("taxonomies", Value::Object(taxonomies));
+
+        let mut res = Vec::new();
+
+        if let Some((path, template)) = &self.index {
+            let mut properties = HashMap::new();
+            properties.insert("taxonomy".to_owned(), Value::String(self.key.clone()));
+
+            let mut context = properties.clone();
+            context.insert("terms".to_owned(), Value::Object(terms));
+
+            res.push(Item {
+                path: PathBuf::from(path),
+                bytes: render(state, template, context)?,
+                properties,
+            });
+        }
+
+        if let Some((path_pattern, template)) = &self.terms {
+            for (slug, (term, items)) in &grouped {
+                let mut properties = HashMap::new();
+                properties.insert("taxonomy".to_owned(), Value::String(self.key.clone()));
+                properties.insert("term".to_owned(), Value::String(term.clone()));
+
+                let path_pattern = path_pattern.replace("{term}", slug);
+                let per_page = self.per_page.unwrap_or(items.len()).max(1);
+
+                res.extend(render_pages(state, items, per_page, &path_pattern, template, properties)?);
+            }
+        }
+
+        Ok(res)
+    }
+}
+
+#[derive(Clone)]
+pub struct CollectInto<P: SingleProcedure, M: MultiProcedure<P>> {
+    p1: PhantomData<P>,
+    prior: M,
+    path: PathBuf,
+    template: String,
+}
+
+#[async_trait(?Send)]
+impl<P: SingleProcedure, M: MultiProcedure<P>> SingleProcedure for CollectInto<P, M> {
+    async fn eval(&self, state: &mut State) -> Result<Item> {
+        let items = self.prior.into_meta(state).await?;
+
+        Ok(Item {
+            path: self.path.clone(),
+            bytes: render(state, &self.template, HashMap::from([("items".to_owned(), items)]))?,
+            properties: HashMap::new(),
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct GroupBy<P, M, F>
+where
+    P: SingleProcedure,
+    M: MultiProcedure<P>,
+    F: Fn(&Item) -> String + Clone,
+{
+    p1: PhantomData<P>,
+    prior: M,
+    func: F,
+}
+
+impl<P, M, F> GroupBy<P, M, F>
+where
+    P: SingleProcedure,
+    M: MultiProcedure<P>,
+    F: Fn(&Item) -> String + Clone,
+{
+    // renders every group into a single item, as a `groups` list of `key` and `items`, in the order their keys first appear
+    pub fn collect_into<S: Into<PathBuf>>(self, path: S, template: &str) -> CollectGroups<P, M, F> {
+        CollectGroups {
+            prior: self,
+            path: path.into(),
+            template: template.to_owned(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CollectGroups<P, M, F>
+where
+    P: SingleProcedure,
+    M: MultiProcedure<P>,
+    F: Fn(&Item) -> String + Clone,
+{
+    prior: GroupBy<P, M, F>,
+    path: PathBuf,
+    template: String,
+}
+
+#[async_trait(?Send)]
+impl<P, M, F> SingleProcedure for CollectGroups<P, M, F>
+where
+    P: SingleProcedure,
+    M: MultiProcedure<P>,
+    F: Fn(&Item) -> String + Clone,
+{
+    async fn eval(&self, state: &mut State) -> Result<Item> {
+        let mut groups: Vec<(String, Vec<Value>)> = Vec::new();
+
+        for item in self.prior.prior.eval(state).await? {
+            let key = (self.prior.func)(&item);
+            let meta = item.into_meta()?;
+
+            match groups.iter_mut().find(|(group, _)| *group == key) {
+                Some((_, items)) => items.push(meta),
+                None => groups.push((key, vec![meta])),
+            }
+        }
+
+        let groups = groups
+            .into_iter()
+            .map(|(key, items)| {
+                let mut group = tera::Map::new();
+                group.insert("key".to_owned(), Value::String(key));
+                group.insert("items".to_owned(), Value::Array(items));
+
+                Value::Object(group)
+            })
+            .collect();
+
+        Ok(Item {
+            path: self.path.clone(),
+            bytes: render(state, &self.template, HashMap::from([("groups".to_owned(), Value::Array(groups))]))?,
+            properties: HashMap::new(),
+        })
+    }
+}
+
+// lowercase letters and digits, with spaces and dashes between them turned into a single dash and anything else
+// into `_` and its hex code, so `C`, `C#` and `C++` stay apart as `c`, `c_23` and `c_2b_2b`
+fn slug(term: &str) -> String {
+    let mut slug = String::new();
+
+    for c in term.to_lowercase().chars() {
+        match c {
+            c if c.is_alphanumeric() => slug.push(c),
+            c if c.is_whitespace() || c == '-' => {
+                if !slug.is_empty() && !slug.ends_with('-') {
+                    slug.push('-');
+                }
+            },
+            c => slug.push_str(&format!("_{:x}", c as u32)),
+        }
+    }
+
+    slug.trim_end_matches('-').to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde_json::json;
+    use tera::Value;
+
+    use crate::{data::State, Item};
+
+    use super::{MultiProcedure, SingleProcedure};
+
+    fn posts() -> Vec<Item> {
+        ["2024-01-01-a.md", "2024-01-02-b.md", "2024-01-03-c.md", "2024-01-04-d.md"]
+            .iter()
+            .enumerate()
+            .map(|(i, path)| Item {
+                path: path.into(),
+                bytes: Vec::new(),
+                properties: HashMap::from([("draft".to_owned(), Value::Bool(i % 2 == 1))]),
+            })
+            .collect()
+    }
+
+    fn names(items: Vec<Item>) -> Vec<String> {
+        items.iter().map(|item| item.get_filename().unwrap()).collect()
+    }
+
+    #[actix_web::test]
+    async fn filter() {
+        let mut state = State::new("dist", "test/templates").unwrap();
+
+        let res = posts()
+            .filter(|item| item.properties.get("draft") != Some(&Value::Bool(true)))
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec!["2024-01-01-a.md", "2024-01-03-c.md"], names(res));
+
+        let res = posts()
+            .filter_property("draft", Value::Bool(true))
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec!["2024-01-02-b.md", "2024-01-04-d.md"], names(res));
+    }
+
+    #[actix_web::test]
+    async fn take_and_skip() {
+        let mut state = State::new("dist", "test/templates").unwrap();
+
+        let res = posts()
+            .sorted()
+            .reversed()
+            .skip(1)
+            .take(2)
+            .chained(|item| item.property("seen", Value::Bool(true)))
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert!(res.iter().all(|item| item.properties["seen"] == Value::Bool(true)));
+        assert_eq!(vec!["2024-01-03-c.md", "2024-01-02-b.md"], names(res));
+    }
+
+    fn pages(key: &str, values: Vec<Value>) -> Vec<Item> {
+        values
+            .into_iter()
+            .enumerate()
+            .map(|(i, value)| Item {
+                path: format!("{}.md", i).into(),
+                bytes: Vec::new(),
+                properties: HashMap::from([(key.to_owned(), value)]),
+            })
+            .collect()
+    }
+
+    #[actix_web::test]
+    async fn sort_by_property() {
+        let mut state = State::new("dist", "test/templates").unwrap();
+
+        let res = pages("weight", vec![json!(10), json!(2), json!(null), json!(2.5), json!(2)])
+            .sort_by_property("weight")
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec!["1.md", "4.md", "3.md", "0.md", "2.md"], names(res));
+
+        let res = pages("date", vec![json!("2024-03-01"), json!("2024-01-02T10:00:00+02:00"), json!("2024-01-02 09:00")])
+            .sort_by_property("date")
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec!["1.md", "2.md", "0.md"], names(res));
+
+        let res = pages("title", vec![json!("zebra"), json!("Émile"), json!("apple"), json!("eagle")])
+            .sort_by_property("title")
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec!["2.md", "3.md", "1.md", "0.md"], names(res));
+
+        // too close together to tell apart as floats
+        let res = pages("id", vec![json!(9007199254740993u64), json!(9007199254740992u64), json!(-1)])
+            .sort_by_property("id")
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec!["2.md", "1.md", "0.md"], names(res));
+    }
+
+    #[actix_web::test]
+    async fn sort_by_property_in() {
+        let mut state = State::new("dist", "test/templates").unwrap();
+        let titles = || pages("title", vec![json!("öl"), json!("zebra"), json!("orm")]);
+
+        let res = titles()
+            .sort_by_property("title")
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec!["0.md", "2.md", "1.md"], names(res));
+
+        // swedish puts ö after z
+        let sort = titles().sort_by_property_in("title", "sv");
+        let res = sort.eval(&mut state).await.unwrap();
+        assert_eq!(vec!["2.md", "1.md", "0.md"], names(res));
+
+        // the collator from the first eval is used again
+        let res = sort.eval(&mut state).await.unwrap();
+        assert_eq!(vec!["2.md", "1.md", "0.md"], names(res));
+
+        let res = titles()
+            .sort_by_property_in("title", "not a locale")
+            .eval(&mut state)
+            .await;
+        assert_eq!("Invalid locale not a locale", res.unwrap_err().to_string());
+    }
+
+    #[actix_web::test]
+    async fn sort_by() {
+        let mut state = State::new("dist", "test/templates").unwrap();
+
+        let res = posts()
+            .sort_by(|a, b| a.properties["draft"].as_bool().cmp(&b.properties["draft"].as_bool()))
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert_eq!(vec!["2024-01-01-a.md", "2024-01-03-c.md", "2024-01-02-b.md", "2024-01-04-d.md"], names(res));
+    }
+
+    #[actix_web::test]
+    async fn paginate() {
+        let mut state = State::new("dist", "test/templates").unwrap();
+
+        let res = posts()
+            .paginate(3, "blog/page/{page}/index.html", "page.txt")
+            .eval(&mut state)
+            .await
+            .unwrap();
+        let pages = res
+            .iter()
+            .map(|page| (page.path.display().to_string(), String::from_utf8(page.bytes.clone()).unwrap()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![
+            (format!("blog/page/1/index.html"), format!("1/2: /2024-01-01-a.md /2024-01-02-b.md /2024-01-03-c.md next /blog/page/2/index.html\n")),
+            (format!("blog/page/2/index.html"), format!("2/2: /2024-01-04-d.md prev /blog/page/1/index.html\n")),
+        ], pages);
+
+        let res = Vec::<Item>::new()
+            .paginate(3, "blog/page/{page}/index.html", "page.txt")
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert_eq!(1, res.len());
+
+        let res = posts()
+            .paginate(4, "blog/index.html", "page.txt")
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert_eq!(1, res.len());
+
+        let res = posts()
+            .paginate(3, "blog/index.html", "page.txt")
+            .eval(&mut state)
+            .await;
+        assert_eq!("Can't paginate 4 items 3 per page into blog/index.html, it has no {page}", res.unwrap_err().to_string());
+    }
+
+    #[actix_web::test]
+    async fn taxonomy() {
+        let mut state = State::new("dist", "test/templates").unwrap();
+        let tags = [json!(["Rust", "web dev"]), json!("Rust"), json!(["web dev", "web dev"]), json!(null)];
+        let items = posts()
+            .into_iter()
+            .zip(tags)
+            .map(|(item, tags)| item.set_property("tags", tags))
+            .collect::<Vec<_>>();
+
+        let res = items
+            .taxonomy("tags")
+            .index("tags/index.html", "terms.txt")
+            .terms("tags/{term}/page/{page}/index.html", "term.txt")
+            .per_page(1)
+            .eval(&mut state)
+            .await
+            .unwrap();
+        let pages = res
+            .iter()
+            .map(|page| (page.path.display().to_string(), String::from_utf8(page.bytes.clone()).unwrap()))
+            .collect::<Vec<_>>();
+
+        assert_eq!(vec![
+            (format!("tags/index.html"), format!("tags: Rust 2 /tags/rust/page/1/index.html web dev 2 /tags/web-dev/page/1/index.html\n")),
+            (format!("tags/rust/page/1/index.html"), format!("Rust 1/2: /2024-01-01-a.md (2 tags)\n")),
+            (format!("tags/rust/page/2/index.html"), format!("Rust 2/2: /2024-01-02-b.md (2 tags)\n")),
+            (format!("tags/web-dev/page/1/index.html"), format!("web dev 1/2: /2024-01-01-a.md (2 tags)\n")),
+            (format!("tags/web-dev/page/2/index.html"), format!("web dev 2/2: /2024-01-03-c.md (2 tags)\n")),
+        ], pages);
+    }
+
+    #[actix_web::test]
+    async fn taxonomy_slugs() {
+        let mut state = State::new("dist", "test/templates").unwrap();
+
+        let res = pages("tags", vec![json!("C"), json!("C#"), json!("C++"), json!("c sharp")])
+            .taxonomy("tags")
+            .terms("tags/{term}.html", "term.txt")
+            .eval(&mut state)
+            .await
+            .unwrap();
+        let paths = res
+            .iter()
+            .map(|page| page.path.display().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(vec!["tags/c.html", "tags/c-sharp.html", "tags/c_23.html", "tags/c_2b_2b.html"], paths);
+
+        let res = pages("tags", vec![json!("Rust"), json!("rust")])
+            .taxonomy("tags")
+            .eval(&mut state)
+            .await;
+        assert_eq!("Terms 'Rust' and 'rust' of taxonomy tags have the same slug", res.unwrap_err().to_string());
+
+        let res = pages("tags", vec![json!(" - ")])
+            .taxonomy("tags")
+            .eval(&mut state)
+            .await;
+        assert_eq!("Term ' - ' of taxonomy tags has nothing to make a path from", res.unwrap_err().to_string());
+    }
+
+    #[actix_web::test]
+    async fn taxonomy_paths() {
+        let mut state = State::new("dist", "test/templates").unwrap();
+
+        let res = pages("tags", vec![json!("meow")])
+            .taxonomy("tags")
+            .terms("tags/index.html", "term.txt")
+            .eval(&mut state)
+            .await;
+        assert_eq!("Can't write the terms of taxonomy tags to tags/index.html, it has no {term}", res.unwrap_err().to_string());
+
+        let res = pages("tags", vec![json!("meow")])
+            .taxonomy("tags")
+            .terms("tags/{term}/index.html", "term.txt")
+            .per_page(10)
+            .eval(&mut state)
+            .await;
+        assert_eq!("Can't paginate the terms of taxonomy tags into tags/{term}/index.html, it has no {page}", res.unwrap_err().to_string());
+    }
+
+    #[actix_web::test]
+    async fn collect_into() {
+        let mut state = State::new("dist", "test/templates").unwrap();
+
+        let res = posts()
+            .take(2)
+            .collect_into("archive.html", "collection.txt")
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert_eq!("archive.html", res.path.display().to_string());
+        assert_eq!("/2024-01-01-a.md /2024-01-02-b.md \n", String::from_utf8(res.bytes).unwrap());
+
+        let res = posts()
+            .reversed()
+            .group_by(|item| match item.properties["draft"] {
+                Value::Bool(true) => format!("drafts"),
+                _ => format!("published"),
+            })
+            .collect_into("archive.html", "groups.txt")
+            .eval(&mut state)
+            .await
+            .unwrap();
+        assert_eq!(
+            "drafts: /2024-01-04-d.md /2024-01-02-b.md\npublished: /2024-01-03-c.md /2024-01-01-a.md\n",
+            String::from_utf8(res.bytes).unwrap(),
+        );
+    }
+}